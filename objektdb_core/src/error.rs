@@ -0,0 +1,161 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::support_mods::field::{SchemaDiff, OID};
+use crate::traits::from_bytes::DecodeError;
+
+///The error type of every fallible operation of objektDB.
+///
+///It lets callers tell apart the failures of the storage engine, for
+///example a database that already exists from an I/O error or from a
+///file that is not an objektDB database at all.
+#[derive(Debug)]
+pub enum Error {
+    ///An I/O operation on the files of the database failed.
+    Io(io::Error),
+    ///The database or table to create already exists. It contains its name.
+    AlreadyExists(String),
+    ///The database, table or object does not exist. It contains its name.
+    NotFound(String),
+    ///The `.db` file doesn't start with `MAGIC_NUMBER`.
+    InvalidMagic,
+    ///The `.db` file was written with a template version this build can't read.
+    UnsupportedVersion(u8),
+    ///The `.db` file uses features this build doesn't know. It contains their bits.
+    UnsupportedFlags(u32),
+    ///The database already contains 255 tables.
+    TableLimit,
+    ///A table name longer than 64 bytes, or a field, type or method name longer than 255 bytes.
+    NameTooLong(String),
+    ///The path can't be used as a database directory.
+    InvalidPath(PathBuf),
+    ///The table has no OIDs left, or its data section reached 4 GB.
+    TableFull(String),
+    ///The value of the field `field` of an object of `table` is `len` bytes long, but a
+    ///field value can't be longer than 255 bytes.
+    ValueTooLong {
+        table: String,
+        field: String,
+        len: usize,
+    },
+    ///The object has no OID field, so it can't be found in its table.
+    MissingOid(String),
+    ///The files of `table` contain invalid data at `offset`.
+    Corrupt {
+        table: String,
+        offset: u64,
+    },
+    ///The catalog of the tables in the `.db` file contains invalid data at `offset`.
+    CorruptCatalog {
+        offset: u64,
+    },
+    ///The field `field` of a record of `table` can't be decoded. `offset` is the
+    ///position of the field in the `.tbl` file, or in the record if it was decoded
+    ///outside of a table.
+    CorruptField {
+        table: String,
+        field: String,
+        offset: u64,
+        error: DecodeError,
+    },
+    ///The schema stored in the `.tbl` file of `table` is not the one of its struct.
+    ///
+    ///The records can be converted to the new schema with `Database::migrate()`.
+    SchemaMismatch {
+        table: String,
+        diff: SchemaDiff,
+    },
+    ///A thread panicked while using the database, or a change to its tables failed
+    ///halfway and couldn't be completed, so its state is unknown. The changes are
+    ///recovered when the database is opened again, once all its handles are dropped.
+    Poisoned(String),
+    ///The database can't be deleted while a handle to it is open. It contains its name.
+    InUse(String),
+    ///The `Ref` to an object of the table is not bound to a database, so it can't be loaded.
+    ///It contains the name of the table.
+    Unbound(String),
+    ///The object `oid` of `table` can't be deleted: the field `field` of the object `by_oid`
+    ///of `by` refers to it, and its `on_delete` policy is `OnDelete::Restrict`.
+    RestrictedDelete {
+        table: String,
+        oid: OID,
+        by: String,
+        by_oid: OID,
+        field: String,
+    },
+    ///The table `table` can't be dropped because the references section of `by` points at it.
+    Referenced {
+        table: String,
+        by: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::AlreadyExists(name) => write!(f, "{} already exists", name),
+            Error::NotFound(name) => write!(f, "{} does not exist", name),
+            Error::InvalidMagic => write!(f, "Invalid database file format"),
+            Error::UnsupportedVersion(version) => write!(f, "Unsupported database version: {}", version),
+            Error::UnsupportedFlags(flags) => write!(f, "Unsupported database features: {:#010x}", flags),
+            Error::TableLimit => write!(f, "Maximum number of tables reached (255)"),
+            Error::NameTooLong(name) => write!(f, "The name {} is too long: table names must be 64 bytes or less, field, type and method names 255 bytes or less", name),
+            Error::InvalidPath(path) => write!(f, "Invalid database path: {}", path.display()),
+            Error::TableFull(table) => write!(f, "Table {} is full", table),
+            Error::ValueTooLong { table, field, len } => write!(
+                f, "The value of field {} of {} is {} bytes long, the maximum is 255", field, table, len
+            ),
+            Error::MissingOid(table) => write!(f, "{} has no OID field", table),
+            Error::Corrupt { table, offset } => write!(f, "Table {} is corrupted at offset {}", table, offset),
+            Error::CorruptCatalog { offset } => write!(f, "The catalog of the database is corrupted at offset {}", offset),
+            Error::CorruptField { table, field, offset, error } => {
+                write!(f, "Table {} is corrupted: field {} at offset {}: {}", table, field, offset, error)
+            },
+            Error::SchemaMismatch { table, diff } => {
+                let names = |fields: &mut dyn Iterator<Item = String>| fields.collect::<Vec<_>>().join(", ");
+
+                write!(f, "The schema of table {} has changed", table)?;
+                if !diff.added.is_empty() {
+                    write!(f, "; added: {}", names(&mut diff.added.iter().map(|field| field.name.clone())))?;
+                }
+                if !diff.removed.is_empty() {
+                    write!(f, "; removed: {}", names(&mut diff.removed.iter().map(|field| field.name.clone())))?;
+                }
+                if !diff.retyped.is_empty() {
+                    write!(f, "; retyped: {}", names(&mut diff.retyped.iter()
+                        .map(|(old, new)| format!("{} ({} -> {})", new.name, old.type_, new.type_))))?;
+                }
+                if diff.reordered {
+                    write!(f, "; fields reordered")?;
+                }
+                Ok(())
+            },
+            Error::Poisoned(db) => write!(f, "Database {} is unusable: an operation was interrupted halfway, drop its handles and open it again", db),
+            Error::InUse(name) => write!(f, "{} is in use", name),
+            Error::Unbound(table) => write!(f, "The reference to an object of {} is not bound to a database", table),
+            Error::RestrictedDelete { table, oid, by, by_oid, field } => write!(
+                f, "Object {} of {} can't be deleted: it's referenced by the field {} of object {} of {}",
+                oid.val, table, field, by_oid.val, by
+            ),
+            Error::Referenced { table, by } => write!(f, "Table {} is referenced by table {}", table, by),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::CorruptField { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
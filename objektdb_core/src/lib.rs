@@ -1,4 +1,7 @@
+pub mod error;
 pub mod storage_engine;
 pub mod support_mods;
 mod tests;
-pub mod traits;
\ No newline at end of file
+pub mod traits;
+
+pub use error::Error;
\ No newline at end of file
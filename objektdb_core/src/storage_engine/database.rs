@@ -0,0 +1,1486 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::file_manager::{
+    self, DbHeader, RawScan, RecordIter, TableEntry, TableFiles, BACKUP_DIR, DB_HEADER_SIZE, RECORD_HEADER_SIZE,
+};
+use super::log_manager::{LogManager, PageWrite};
+use super::raw_record::RawRecord;
+use crate::error::Error;
+use crate::support_mods::field::{Field, OnDelete, TableSchema, OID};
+use crate::support_mods::support_functions::string_padding;
+use crate::traits::{objekt::Objekt, to_bytes::ToBytes};
+
+/// Handle to an open objektDB database.
+///
+/// A database is a directory containing the `<name>.db` file, plus a `.tbl`
+/// file and a `_bucket.bin` file for each table. The handle owns the header of
+/// the `.db` file and the files of the tables used so far, and every operation
+/// on the database goes through it, so it can live anywhere on the filesystem
+/// and several databases can be open at the same time.
+///
+/// Cloning a `Database` is cheap: the clones share the same open files. Opening
+/// a database that already has a handle returns a clone of it, so there is only
+/// one set of open files and one log for each database in the process.
+///
+/// # Example
+/// ```ignore
+/// let db = Database::create("/var/lib/app/my_database")?;
+/// db.create_table("users".to_string(), vec![], fields, vec![])?;
+///
+/// let oid = db.insert_record("users".to_string(), user.to_bytes()?)?;
+/// ```
+#[derive(Clone)]
+pub struct Database {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    name: String,
+    dir: PathBuf,
+    state: Mutex<State>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        // The entry of this handle can't be upgraded anymore
+        open_databases().retain(|(_, inner)| inner.strong_count() > 0);
+    }
+}
+
+/// The databases with an open handle, by directory (see `registry_key()`), with their
+/// `Inner` shared by the handle and its clones.
+static OPEN_DATABASES: Mutex<Vec<(PathBuf, Weak<Inner>)>> = Mutex::new(Vec::new());
+
+struct State {
+    /// The path of the `.db` file, which `write_catalog()` replaces
+    db_path: PathBuf,
+    /// The write-ahead log of the changes to the records
+    log: LogManager,
+    /// Set when a commit failed halfway and couldn't be completed from the log: the
+    /// tables are in an unknown state until the database is opened again
+    broken: bool,
+    header: DbHeader,
+    /// The catalog written after the header of the `.db` file
+    catalog: Vec<TableEntry>,
+    tables: HashMap<String, TableFiles>,
+    /// The rows of the join tables of the `RefSet`s read so far, see `join_rows()`
+    join_rows: HashMap<String, JoinRows>,
+}
+
+/// The rows of a join table grouped by owner: the OID of each row and of its target,
+/// in the order in which they were added.
+type JoinRows = HashMap<i32, Vec<(OID, OID)>>;
+
+/// The differences between the catalog of a database and the `.tbl` files in its
+/// directory, returned by `Database::check_catalog()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CatalogCheck {
+    /// The tables in the catalog whose `.tbl` file doesn't exist.
+    pub missing: Vec<String>,
+    /// The `.tbl` files of the directory that are not in the catalog.
+    pub orphaned: Vec<String>,
+    /// The tables whose `.tbl` header has a schema other than the one in the catalog.
+    pub changed: Vec<String>,
+}
+
+impl CatalogCheck {
+    /// Whether the catalog and the files agree.
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.orphaned.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl Database {
+    /// Creates a new database in the directory `path`, which must not exist.
+    ///
+    /// The name of the database is the last component of the path: the directory
+    /// will contain the `<name>.db` file (see `file_manager::create_db()` for its header).
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Database, Error> {
+        let dir = path.as_ref().to_path_buf();
+        let name = db_name(&dir)?;
+
+        fs::create_dir(&dir).map_err(|e| match e.kind() {
+            ErrorKind::AlreadyExists => Error::AlreadyExists(format!("Database {}", name)),
+            _ => Error::Io(e),
+        })?;
+
+        let db_file_path = dir.join(format!("{}.db", name));
+
+        let mut db_file = match OpenOptions::new().read(true).write(true).create_new(true).open(&db_file_path) {
+            Err(e) => {
+                let _ = fs::remove_dir(&dir); // pulizia
+                return Err(Error::Io(e));
+            },
+            Ok(file) => file,
+        };
+
+        let header = DbHeader::new();
+
+        db_file.write_all(&header.to_bytes())?;
+
+        let log = LogManager::open(&dir, &name)?;
+
+        Ok(Database::new(&mut open_databases(), name, dir, log, header, Vec::new()))
+    }
+
+    /// Opens the existing database in the directory `path`.
+    ///
+    /// If the database already has a handle in this process, whatever path it was
+    /// opened with, a clone of that handle is returned.
+    ///
+    /// The header of the `.db` file is validated before anything else is read, then the
+    /// catalog of the tables that follows it.
+    ///
+    /// The changes to the records that were logged but not applied to the tables
+    /// when the database was last used (after a crash) are applied before the tables
+    /// are read, and the incomplete ones are discarded (see `LogManager::recover()`).
+    /// So are the migrations interrupted while their new files replaced the old ones
+    /// (see `finish_migration()`), and the renames and drops of tables interrupted
+    /// halfway (see `finish_rename()` and `finish_drop()`).
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NotFound` if there is no database in `path`, `Error::InvalidMagic`
+    /// if the `.db` file is not an objektDB database, `Error::UnsupportedVersion` if it was
+    /// written with a format version other than `DB_VERSION` and `Error::UnsupportedFlags`
+    /// if it uses features that are not in `SUPPORTED_FLAGS`, `Error::CorruptCatalog` if the
+    /// catalog is missing or malformed.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Database, Error> {
+        let dir = path.as_ref().to_path_buf();
+        let name = db_name(&dir)?;
+
+        let db_file_path = dir.join(format!("{}.db", name));
+
+        if !db_file_path.exists() {
+            return Err(Error::NotFound(format!("Database {}", name)));
+        }
+
+        // Held until the handle is registered, so that the database is opened only once
+        let mut open = open_databases();
+
+        if let Some(inner) = live_handle(&open, &registry_key(&dir)) {
+            return Ok(Database { inner });
+        }
+
+        let mut db_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&db_file_path)?;
+
+        let mut buffer: Vec<u8> = Vec::with_capacity(DB_HEADER_SIZE);
+
+        db_file.read_to_end(&mut buffer)?;
+
+        let header = DbHeader::from_bytes(&buffer)?;
+
+        let mut log = LogManager::open(&dir, &name)?;
+        log.recover(&dir)?;
+
+        let catalog = file_manager::parse_catalog(&buffer[DB_HEADER_SIZE..], header.num_of_tables)?;
+
+        let db = Database::new(&mut open, name, dir, log, header, catalog);
+
+        if let Err(e) = db.finish_changes() {
+            // Dropping the handle unregisters it
+            drop(open);
+            return Err(e);
+        }
+
+        Ok(db)
+    }
+
+    /// Opens the database in the directory `path`, creating it if it doesn't exist.
+    pub fn open_or_create<P: AsRef<Path>>(path: P) -> Result<Database, Error> {
+        let dir = path.as_ref();
+
+        if dir.join(format!("{}.db", db_name(dir)?)).exists() {
+            Database::open(dir)
+        } else {
+            Database::create(dir)
+        }
+    }
+
+    /// Deletes the database in the directory `path`, with all its tables.
+    ///
+    /// The `.tbl` and `_bucket.bin` files of each table, the `.db` file and its log are
+    /// removed, then the directory itself, together with the backups of `reinitialize_table()`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NotFound` if there is no database in `path`, `Error::InUse` if a
+    /// `Database` handle to it is still alive (in this or another thread), and the errors of
+    /// `open()` if the `.db` file is not a valid database: nothing is removed in these cases.
+    pub fn delete<P: AsRef<Path>>(path: P) -> Result<(), Error> {
+        let dir = path.as_ref().to_path_buf();
+        let name = db_name(&dir)?;
+
+        let db_file_path = dir.join(format!("{}.db", name));
+
+        if !db_file_path.exists() {
+            return Err(Error::NotFound(format!("Database {}", name)));
+        }
+
+        // Held until the end, so that the database can't be opened while it's deleted
+        let open = open_databases();
+
+        // Not upgraded: dropping the last handle here would lock `OPEN_DATABASES` again
+        let key = registry_key(&dir);
+        if open.iter().any(|(dir, inner)| *dir == key && inner.strong_count() > 0) {
+            return Err(Error::InUse(format!("Database {}", name)));
+        }
+
+        // Don't delete a directory that is not a database
+        let content = fs::read(&db_file_path)?;
+        let header = DbHeader::from_bytes(&content)?;
+
+        let catalog = file_manager::parse_catalog(&content[DB_HEADER_SIZE..], header.num_of_tables)?;
+
+        for entry in catalog {
+            remove_if_exists(&dir.join(format!("{}.tbl", entry.name)))?;
+            remove_if_exists(&dir.join(format!("{}_bucket.bin", entry.name)))?;
+        }
+
+        remove_if_exists(&dir.join(format!("{}.wal", name)))?;
+        fs::remove_file(db_file_path)?;
+        fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    /// Creates the handle of a database and registers it in `open`, the locked `OPEN_DATABASES`.
+    fn new(
+        open: &mut Vec<(PathBuf, Weak<Inner>)>,
+        name: String,
+        dir: PathBuf,
+        log: LogManager,
+        header: DbHeader,
+        catalog: Vec<TableEntry>
+    ) -> Database {
+        let key = registry_key(&dir);
+        let db_path = dir.join(format!("{}.db", name));
+
+        let db = Database {
+            inner: Arc::new(Inner {
+                name,
+                dir,
+                state: Mutex::new(State {
+                    db_path,
+                    log,
+                    broken: false,
+                    header,
+                    catalog,
+                    tables: HashMap::new(),
+                    join_rows: HashMap::new(),
+                }),
+            }),
+        };
+
+        // A handle being dropped in another thread may still have its entry
+        open.retain(|(dir, inner)| *dir != key || inner.strong_count() > 0);
+        open.push((key, Arc::downgrade(&db.inner)));
+
+        db
+    }
+
+    /// The name of the database.
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    /// The directory of the database.
+    pub fn path(&self) -> &Path {
+        &self.inner.dir
+    }
+
+    /// The header of the `.db` file.
+    pub fn header(&self) -> Result<DbHeader, Error> {
+        Ok(self.lock()?.header)
+    }
+
+    /// The tables of the database, as listed in the catalog of the `.db` file.
+    ///
+    /// They're in the order in which they were created.
+    pub fn tables(&self) -> Result<Vec<TableEntry>, Error> {
+        Ok(self.lock()?.catalog.clone())
+    }
+
+    /// Compares the catalog with the `.tbl` files in the database directory.
+    ///
+    /// It finds the tables whose files were removed, the `.tbl` files that don't belong
+    /// to any table of the catalog and the tables whose schema was changed outside
+    /// of this database.
+    pub fn check_catalog(&self) -> Result<CatalogCheck, Error> {
+        let mut state = self.lock()?;
+        let dir = &self.inner.dir;
+
+        let mut check = CatalogCheck::default();
+
+        for entry in state.catalog.clone() {
+            if !dir.join(&entry.file_name).exists() {
+                check.missing.push(entry.name);
+            } else if Self::table(&mut state, dir, &entry.name)?.read_schema()?.schema_hash() != entry.schema_hash {
+                check.changed.push(entry.name);
+            }
+        }
+
+        check.orphaned = table_names(dir)?.into_iter()
+            .map(|name| format!("{}.tbl", name))
+            .filter(|file_name| !state.catalog.iter().any(|entry| entry.file_name == *file_name))
+            .collect();
+
+        Ok(check)
+    }
+
+    /// Creates a new table in the database.
+    ///
+    /// `references` are the names of the tables referenced by this one, they're written
+    /// in the references section of the header. See `file_manager::create_table()` for
+    /// the layout of the `.tbl` file.
+    /// The table is added to the catalog and the `num_of_tables` field of the `.db` header
+    /// is incremented.
+    pub fn create_table(
+        &self,
+        table_name: String,
+        references: Vec<String>,
+        fields: Vec<Field>,
+        methods_names: Vec<String>
+    ) -> Result<(), Error> {
+        let mut state = self.lock()?;
+
+        if state.header.num_of_tables == 255 {
+            return Err(Error::TableLimit);
+        }
+
+        let schema = TableSchema {
+            name: table_name.clone(),
+            last_oid: OID { val: 0 },
+            version: 1,
+            references,
+            fields,
+            methods: methods_names,
+        };
+        let tbl_file = file_manager::table_file_bytes(&schema)?;
+
+        let tbl_path = self.inner.dir.join(format!("{}.tbl", table_name));
+        if tbl_path.exists() {
+            return Err(Error::AlreadyExists(format!("Table {}", table_name)));
+        }
+
+        File::create(tbl_path)?.write_all(&tbl_file)?;
+        File::create(self.inner.dir.join(format!("{}_bucket.bin", table_name)))?;
+
+        let id = state.catalog.iter().map(|entry| entry.id).max().unwrap_or(0) + 1;
+        state.catalog.push(TableEntry::new(id, &schema));
+
+        Self::write_catalog(&mut state) // Also increments the number of tables
+    }
+
+    /// Deletes a table, with its `.tbl` and `_bucket.bin` files.
+    ///
+    /// The table is removed from the catalog and the `num_of_tables` field of the `.db`
+    /// header is decremented, then its files are deleted. A drop interrupted by a crash is
+    /// finished by `open()` (see `finish_drop()`).
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NotFound` if the table does not exist, `Error::Referenced` if another
+    /// table has it in its references section: nothing is removed in this case.
+    pub fn drop_table(&self, table_name: &str) -> Result<(), Error> {
+        let mut state = self.lock()?;
+        let dir = &self.inner.dir;
+
+        Self::table(&mut state, dir, table_name)?;
+
+        if let Some(by) = Self::referencing_tables(&mut state, dir, table_name)?.into_iter().next() {
+            return Err(Error::Referenced { table: table_name.to_string(), by });
+        }
+
+        // From here on the drop is finished by open() if it's interrupted
+        File::create(marker(dir, table_name, DROP_MARKER))?.sync_all()?;
+
+        Self::finish_drop(&mut state, dir, table_name)
+    }
+
+    /// Removes a table from the catalog, then deletes its files and the marker of the drop.
+    ///
+    /// The files are deleted after the catalog is written, and each step is skipped if
+    /// it was already done, so `open()` can call this again after a crash.
+    fn finish_drop(state: &mut State, dir: &Path, table_name: &str) -> Result<(), Error> {
+        // Close the files before removing them
+        state.tables.remove(table_name);
+        state.join_rows.remove(table_name);
+
+        if state.catalog.iter().any(|entry| entry.name == table_name) {
+            state.catalog.retain(|entry| entry.name != table_name);
+            Self::write_catalog(state)?;
+        }
+
+        remove_if_exists(&dir.join(format!("{}.tbl", table_name)))?;
+        remove_if_exists(&dir.join(format!("{}_bucket.bin", table_name)))?;
+
+        fs::remove_file(marker(dir, table_name, DROP_MARKER))?;
+
+        Ok(())
+    }
+
+    /// Renames a table.
+    ///
+    /// The name is changed in the `.tbl` header, in the names of the `.tbl` and
+    /// `_bucket.bin` files and in the catalog, and the tables that point at it get the
+    /// new name in their references section and in the types of their `Ref` fields.
+    ///
+    /// The new name is written first to the marker `<table>.rename`: a rename interrupted
+    /// by a crash is finished by `open()` (see `finish_rename()`).
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NotFound` if the table does not exist, `Error::AlreadyExists` if
+    /// there is already a table called `new_name` and `Error::NameTooLong` if `new_name`
+    /// is longer than 64 bytes.
+    pub fn rename_table(&self, table_name: &str, new_name: &str) -> Result<(), Error> {
+        let mut state = self.lock()?;
+        let dir = &self.inner.dir;
+
+        Self::table(&mut state, dir, table_name)?;
+
+        let new_tbl_path = dir.join(format!("{}.tbl", new_name));
+        if new_tbl_path.exists() {
+            return Err(Error::AlreadyExists(format!("Table {}", new_name)));
+        }
+
+        // Fails with Error::NameTooLong before anything is written
+        let mut new_name_bytes = Vec::new();
+        string_padding(&mut new_name_bytes, new_name.to_string(), 64)?;
+
+        // From here on the rename is finished by open() if it's interrupted
+        let mut rename_marker = File::create(marker(dir, table_name, RENAME_MARKER))?;
+        rename_marker.write_all(&new_name_bytes)?;
+        rename_marker.sync_all()?;
+
+        Self::finish_rename(&mut state, dir, table_name, new_name)
+    }
+
+    /// Renames a table and retargets the tables that point at it, then removes the marker
+    /// of the rename.
+    ///
+    /// The `.tbl` file gets the new header before it's renamed, then the `_bucket.bin`
+    /// file is renamed, then the catalog and the other tables are updated. Each step is
+    /// skipped if it was already done, so `open()` can call this again after a crash.
+    fn finish_rename(state: &mut State, dir: &Path, table_name: &str, new_name: &str) -> Result<(), Error> {
+        let tbl_path = dir.join(format!("{}.tbl", table_name));
+
+        if tbl_path.exists() {
+            let table = Self::table(state, dir, table_name)?;
+
+            // A table can refer to itself
+            let schema = table.read_schema()?;
+            let new_schema = TableSchema {
+                name: new_name.to_string(),
+                ..retarget_references(schema, table_name, new_name)
+            };
+
+            table.write_header(&new_schema)?;
+            table.sync()?;
+        }
+
+        // Close the files before renaming them
+        state.tables.remove(table_name);
+        state.join_rows.remove(table_name);
+
+        for suffix in [".tbl", "_bucket.bin"] {
+            let old_path = dir.join(format!("{}{}", table_name, suffix));
+
+            if old_path.exists() {
+                fs::rename(old_path, dir.join(format!("{}{}", new_name, suffix)))?;
+            }
+        }
+
+        if let Some(entry) = state.catalog.iter_mut().find(|entry| entry.name == table_name) {
+            entry.name = new_name.to_string();
+            entry.file_name = format!("{}.tbl", new_name);
+        }
+        let new_schema = Self::table(state, dir, new_name)?.read_schema()?;
+        Self::update_catalog(state, &new_schema)?;
+
+        for other in Self::referencing_tables(state, dir, table_name)? {
+            let table = Self::table(state, dir, &other)?;
+            let schema = retarget_references(table.read_schema()?, table_name, new_name);
+
+            table.write_header(&schema)?;
+            Self::update_catalog(state, &schema)?;
+        }
+
+        fs::remove_file(marker(dir, table_name, RENAME_MARKER))?;
+
+        Ok(())
+    }
+
+    /// Creates a table if it doesn't exist, otherwise checks that it has the given fields.
+    ///
+    /// It's called by `Objekt::new()` every time a database is opened, so that a struct
+    /// that changed since its table was created is detected before its records are read.
+    ///
+    /// If `fields` are the stored ones followed by new fields with a default value, the new
+    /// fields are added to the stored schema and its version is incremented: the records
+    /// written before are not touched, their missing fields are decoded with the defaults.
+    ///
+    /// The references section is replaced with `references`, and the `on_delete` policies of
+    /// the fields with the ones in `fields`, if they're different.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::SchemaMismatch` with the fields added, removed and retyped if the
+    /// schema of the existing table is not `fields`. Its records can be converted with
+    /// `migrate()`.
+    pub fn ensure_table(
+        &self,
+        table_name: String,
+        references: Vec<String>,
+        fields: Vec<Field>,
+        methods_names: Vec<String>
+    ) -> Result<(), Error> {
+        if !self.inner.dir.join(format!("{}.tbl", table_name)).exists() {
+            return self.create_table(table_name, references, fields, methods_names);
+        }
+
+        let mut state = self.lock()?;
+
+        let table = Self::table(&mut state, &self.inner.dir, &table_name)?;
+        let schema = table.read_schema()?;
+        let diff = schema.diff(&fields);
+
+        if diff.is_empty() {
+            if schema.references == references && schema.fields == fields {
+                return Ok(());
+            }
+
+            // Only the references or the on_delete policies changed, the records are the same
+            let schema = TableSchema { references, fields, ..schema };
+            table.write_header(&schema)?;
+
+            return Self::update_catalog(&mut state, &schema);
+        }
+
+        // New fields with a default value at the end: the old records stay as they are
+        if schema.is_extended_by(&fields) {
+            let schema = TableSchema {
+                version: schema.version.saturating_add(1),
+                references,
+                fields,
+                ..schema
+            };
+            table.write_header(&schema)?;
+
+            return Self::update_catalog(&mut state, &schema);
+        }
+
+        Err(Error::SchemaMismatch { table: table_name, diff })
+    }
+
+    /// Converts the records of the table of `T` to the current fields of `T`.
+    ///
+    /// Each live record is read with the schema stored in the `.tbl` file and passed to
+    /// `migration` as a `RawRecord`, which returns the new version of the object. The
+    /// new records keep their OIDs and are written to a new copy of the table, with the
+    /// schema of `T`, that replaces the old one only if every record was converted: if
+    /// `migration` returns an error, the table is left as it was. If the process stops
+    /// while the old files are replaced, `open()` completes the replacement.
+    ///
+    /// Nothing is done if the schema of the table is already the one of `T`.
+    ///
+    /// # Example
+    /// ```ignore
+    /// match Person::new(&db) {
+    ///     Err(Error::SchemaMismatch { .. }) => db.migrate(|old: RawRecord| {
+    ///         Ok(Person {
+    ///             id: old.oid(),
+    ///             name: Primitive { val: old.get("name")? },
+    ///             email: Primitive { val: String::new() },
+    ///         })
+    ///     })?,
+    ///     result => result?,
+    /// }
+    /// ```
+    pub fn migrate<T, F>(&self, mut migration: F) -> Result<(), Error>
+    where
+        T: Objekt,
+        F: FnMut(RawRecord) -> Result<T, Error>,
+    {
+        let table_name = T::get_table_name();
+        let mut state = self.lock()?;
+
+        let table = Self::table(&mut state, &self.inner.dir, &table_name)?;
+        let schema = table.read_schema()?;
+
+        if schema.diff(&T::get_fields()).is_empty() {
+            return Ok(());
+        }
+
+        let new_tbl_path = self.inner.dir.join(format!("{}.tbl.migration", table_name));
+        let new_bucket_path = self.inner.dir.join(format!("{}_bucket.bin.migration", table_name));
+
+        if let Err(e) = copy_migrated(table, &schema, &new_tbl_path, &new_bucket_path, &mut migration) {
+            let _ = fs::remove_file(&new_tbl_path);
+            let _ = fs::remove_file(&new_bucket_path);
+            return Err(e);
+        }
+
+        // From here on the migration is finished by open() if it's interrupted
+        File::create(marker(&self.inner.dir, &table_name, MIGRATION_MARKER))?.sync_all()?;
+
+        Self::finish_migration(&mut state, &self.inner.dir, &table_name)
+    }
+
+    /// Moves the new files written by `migrate()` over the old ones and updates the
+    /// catalog, then removes the marker of the migration.
+    ///
+    /// The marker is created once both new files are complete, and the `.tbl` and the
+    /// `_bucket.bin` file can't be renamed together: a crash between the two renames
+    /// would leave the new index over the old bucket. With the marker, `open()` calls this
+    /// again and the renames that didn't happen are done.
+    fn finish_migration(state: &mut State, dir: &Path, table_name: &str) -> Result<(), Error> {
+        // Close the old files before replacing them
+        state.tables.remove(table_name);
+        state.join_rows.remove(table_name);
+
+        for file_name in [format!("{}_bucket.bin", table_name), format!("{}.tbl", table_name)] {
+            let new_path = dir.join(format!("{}.migration", file_name));
+
+            if new_path.exists() {
+                fs::rename(new_path, dir.join(file_name))?;
+            }
+        }
+
+        let schema = Self::table(state, dir, table_name)?.read_schema()?;
+        Self::update_catalog(state, &schema)?;
+
+        fs::remove_file(marker(dir, table_name, MIGRATION_MARKER))?;
+
+        Ok(())
+    }
+
+    /// Finishes the migrations, renames and drops of tables that were interrupted after
+    /// their markers were written, see `finish_migration()`, `finish_rename()` and
+    /// `finish_drop()`.
+    fn finish_changes(&self) -> Result<(), Error> {
+        let mut state = self.lock()?;
+        let dir = &self.inner.dir;
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+
+            if let Some(table_name) = file_name.strip_suffix(MIGRATION_MARKER) {
+                Self::finish_migration(&mut state, dir, table_name)?;
+            } else if let Some(table_name) = file_name.strip_suffix(RENAME_MARKER) {
+                let content = fs::read(&path)?;
+                let new_name = std::str::from_utf8(&content).unwrap_or("").trim_start_matches('\0');
+
+                // The new name was not completely written: nothing was renamed yet
+                if content.len() != 64 || new_name.is_empty() {
+                    fs::remove_file(&path)?;
+                } else {
+                    Self::finish_rename(&mut state, dir, table_name, new_name)?;
+                }
+            } else if let Some(table_name) = file_name.strip_suffix(DROP_MARKER) {
+                Self::finish_drop(&mut state, dir, table_name)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replaces a table with an empty one that has the given schema.
+    ///
+    /// The old `.tbl` and `_bucket.bin` files are kept in a new directory of
+    /// `backups/` in the database directory, named after the table and the time of the
+    /// call, whose path is returned. The new files are written next to the old ones and
+    /// renamed over them, the `.tbl` file first: at any time the table is either the old
+    /// one or the new one, and the `num_of_tables` field of the `.db` header stays the same.
+    ///
+    /// The version of the schema is incremented and `last_OID` is kept: the new objects
+    /// get OIDs never used by the old ones, which the references in other tables may
+    /// still point at.
+    ///
+    /// **Caution**: the new table has no records. To keep them, convert them to the new
+    /// struct with `migrate()` instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NotFound` if the table does not exist.
+    pub fn reinitialize_table(
+        &self,
+        table_name: String,
+        references: Vec<String>,
+        fields: Vec<Field>,
+        methods_names: Vec<String>
+    ) -> Result<PathBuf, Error> {
+        let mut state = self.lock()?;
+
+        let schema = Self::table(&mut state, &self.inner.dir, &table_name)?.read_schema()?;
+
+        let new_schema = TableSchema {
+            name: table_name.clone(),
+            last_oid: schema.last_oid,
+            version: schema.version.saturating_add(1),
+            references,
+            fields,
+            methods: methods_names,
+        };
+        let tbl_file = file_manager::table_file_bytes(&new_schema)?;
+
+        let tbl_path = self.inner.dir.join(format!("{}.tbl", table_name));
+        let bucket_path = self.inner.dir.join(format!("{}_bucket.bin", table_name));
+        let new_tbl_path = self.inner.dir.join(format!("{}.tbl.reinit", table_name));
+        let new_bucket_path = self.inner.dir.join(format!("{}_bucket.bin.reinit", table_name));
+
+        let written = File::create(&new_tbl_path)
+            .and_then(|mut file| { file.write_all(&tbl_file)?; file.sync_all() })
+            .and_then(|_| File::create(&new_bucket_path)?.sync_all());
+
+        if let Err(e) = written {
+            let _ = fs::remove_file(&new_tbl_path);
+            let _ = fs::remove_file(&new_bucket_path);
+            return Err(Error::Io(e));
+        }
+
+        // Close the old files before replacing them
+        state.tables.remove(&table_name);
+        state.join_rows.remove(&table_name);
+
+        let backup = match self.backup_table(&table_name, &tbl_path, &bucket_path) {
+            Ok(backup) => backup,
+            Err(e) => {
+                let _ = fs::remove_file(&new_tbl_path);
+                let _ = fs::remove_file(&new_bucket_path);
+                return Err(e);
+            },
+        };
+
+        // An empty index over the old bucket is still a valid table, the opposite is not
+        fs::rename(&new_tbl_path, tbl_path)?;
+        fs::rename(&new_bucket_path, bucket_path)?;
+
+        Self::update_catalog(&mut state, &new_schema)?;
+
+        Ok(backup)
+    }
+
+    /// Copies the files of a table into a new directory of `backups/` and returns its path.
+    ///
+    /// The files are hard linked when possible, so that the backup costs no space
+    /// until the table is replaced.
+    fn backup_table(&self, table_name: &str, tbl_path: &Path, bucket_path: &Path) -> Result<PathBuf, Error> {
+        let backups = self.inner.dir.join(BACKUP_DIR);
+        fs::create_dir_all(&backups)?;
+
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_millis())
+            .unwrap_or(0);
+
+        // Two calls in the same millisecond get different directories
+        let mut backup = backups.join(format!("{}-{}", table_name, millis));
+        let mut attempt = 1;
+        while let Err(e) = fs::create_dir(&backup) {
+            if e.kind() != ErrorKind::AlreadyExists {
+                return Err(Error::Io(e));
+            }
+            backup = backups.join(format!("{}-{}-{}", table_name, millis, attempt));
+            attempt += 1;
+        }
+
+        for path in [tbl_path, bucket_path] {
+            let target = backup.join(path.file_name().unwrap());
+            if fs::hard_link(path, &target).is_err() {
+                fs::copy(path, &target)?;
+            }
+        }
+
+        Ok(backup)
+    }
+
+    /// Reads the schema of a table from the header of its `.tbl` file.
+    ///
+    /// The fields are returned in the order in which they are stored in the records.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NotFound` if the table does not exist, `Error::Corrupt` if its header is malformed.
+    pub fn read_schema(&self, table_name: &str) -> Result<TableSchema, Error> {
+        self.with_table(table_name, |table| table.read_schema())
+    }
+
+    /// Appends a record to a table and returns the OID assigned to it.
+    ///
+    /// The record is indexed through the `fx_hash16()` slot of its OID and a node of
+    /// the `_bucket.bin` file, as described in `docs/architecture.md`.
+    ///
+    /// # Arguments
+    ///
+    /// * `table_name` - The name of the table, as passed to `create_table()`.
+    /// * `record` - The encoded record, as returned by `Objekt::to_bytes()`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NotFound` if the table does not exist, `Error::TableFull` if all the
+    /// OIDs have been used, `Error::Io` if an I/O error occurs.
+    pub fn insert_record(&self, table_name: String, record: Vec<u8>) -> Result<OID, Error> {
+        self.write_table(&table_name, |table| table.insert(&record))
+    }
+
+    /// Reads a record of a table through the index, given its OID.
+    ///
+    /// Returns `Ok(None)` if there is no record with that OID.
+    pub fn get_record(&self, table_name: String, oid: OID) -> Result<Option<Vec<u8>>, Error> {
+        self.with_table(&table_name, |table| table.get(oid))
+            .map(|record| record.map(|(_, record)| record))
+    }
+
+    /// Reads the object of type `T` with the given OID, through the index of its table.
+    ///
+    /// The record is decoded with `Objekt::record_from_bytes()`, its OID field is set and
+    /// its references are bound to this database, so that they can be loaded.
+    /// Returns `Ok(None)` if there is no object with that OID, `Error::CorruptField`
+    /// if one of its fields can't be decoded.
+    pub fn get_object<T: Objekt>(&self, oid: OID) -> Result<Option<T>, Error> {
+        let table_name = T::get_table_name();
+
+        let Some((record_start, record)) = self.with_table(&table_name, |table| table.get(oid))? else {
+            return Ok(None);
+        };
+
+        file_manager::decode_record(self, oid, record_start, record).map(Some)
+    }
+
+    /// Opens a scan of all the records of the table of `T`.
+    ///
+    /// The records are not read here: they are decoded one at a time while the
+    /// returned `RecordIter` is consumed.
+    pub fn get_records<T: Objekt>(&self) -> Result<RecordIter<T>, Error> {
+        self.with_table(&T::get_table_name(), |table| RecordIter::open(table, self.clone()))
+    }
+
+    /// Counts the live records of a table.
+    ///
+    /// The data section is scanned, without decoding the records.
+    pub fn count_records(&self, table_name: String) -> Result<usize, Error> {
+        let mut scan = self.with_table(&table_name, |table| RawScan::open(table))?;
+
+        let mut count = 0;
+        while scan.next_raw()?.is_some() {
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Replaces a record of a table with a new version of it, keeping its OID.
+    ///
+    /// The record is rewritten in place if it fits in the space of the old one,
+    /// otherwise it's moved to the end of the data section.
+    ///
+    /// Returns `Ok(false)` if there is no record with that OID.
+    pub fn update_record(&self, table_name: String, oid: OID, record: Vec<u8>) -> Result<bool, Error> {
+        self.write_table(&table_name, |table| table.update(oid, &record))
+    }
+
+    /// Deletes a record of a table, given its OID.
+    ///
+    /// The records of other tables that refer to it are found through the catalog: the
+    /// tables with this one in their references section, and their `Ref` fields pointing
+    /// at it. Each of them is handled according to the `on_delete` policy of its field:
+    /// it's deleted too (`Cascade`, and so on with the records referring to it), its
+    /// reference is set to null (`SetNull`) or the deletion is refused (`Restrict`).
+    /// Nothing is changed if the deletion is refused.
+    ///
+    /// Returns `Ok(false)` if there is no record with that OID.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::RestrictedDelete` with the first record that prevents the deletion.
+    pub fn delete_record(&self, table_name: String, oid: OID) -> Result<bool, Error> {
+        let mut state = self.lock()?;
+        let dir = &self.inner.dir;
+
+        if Self::table(&mut state, dir, &table_name)?.get(oid)?.is_none() {
+            return Ok(false);
+        }
+
+        let plan = Self::plan_delete(&mut state, dir, &table_name, oid)?;
+
+        // A restricting record can still be deleted by another cascade
+        if let Some(restrict) = plan.restrict.into_iter().find(|(by, by_oid, ..)| !plan.deleted.contains(&(by.clone(), *by_oid))) {
+            let (by, by_oid, field, table, oid) = restrict;
+            return Err(Error::RestrictedDelete { table, oid, by, by_oid, field });
+        }
+
+        // The cascades and the set-nulls are committed together with the deletion
+        Self::transaction(&mut state, dir, |state| {
+            for (table_name, oid, field) in plan.set_null {
+                if !plan.deleted.contains(&(table_name.clone(), oid)) {
+                    Self::set_null(state, dir, &table_name, oid, &field)?;
+                }
+            }
+
+            for (table_name, oid) in plan.delete {
+                Self::table(state, dir, &table_name)?.delete(oid)?;
+            }
+
+            Ok(true)
+        })
+    }
+
+    /// Finds the records to delete with the record `oid` of `table_name`, and what has
+    /// to be done to the records referring to them.
+    ///
+    /// The cascades are followed with a work list instead of recursion. Each referencing
+    /// table is scanned once, the first time it's needed, and its records are kept by the
+    /// values of their foreign keys, so a long chain of cascades doesn't read the same
+    /// table again for each of its records.
+    fn plan_delete(state: &mut State, dir: &Path, table_name: &str, oid: OID) -> Result<DeletePlan, Error> {
+        let mut plan = DeletePlan::default();
+        let mut foreign_keys: HashMap<String, Vec<(String, Field)>> = HashMap::new();
+        let mut referrers: HashMap<String, Referrers> = HashMap::new();
+
+        plan.add(table_name, oid);
+        let mut pending = vec![(table_name.to_string(), oid)];
+
+        while let Some((table_name, oid)) = pending.pop() {
+            if !foreign_keys.contains_key(&table_name) {
+                let found = Self::foreign_keys(state, dir, &table_name)?;
+                foreign_keys.insert(table_name.clone(), found);
+            }
+
+            for (referencing, field) in &foreign_keys[&table_name] {
+                if !referrers.contains_key(referencing) {
+                    let found = scan_referrers(Self::table(state, dir, referencing)?)?;
+                    referrers.insert(referencing.clone(), found);
+                }
+
+                let Some(records) = referrers[referencing].get(&(field.name.clone(), oid.val)) else {
+                    continue;
+                };
+
+                for &record in records {
+                    match field.on_delete {
+                        OnDelete::Restrict => plan.restrict.push((
+                            referencing.clone(), record, field.name.clone(), table_name.clone(), oid,
+                        )),
+                        OnDelete::Cascade => if plan.add(referencing, record) {
+                            pending.push((referencing.clone(), record));
+                        },
+                        OnDelete::SetNull => plan.set_null.push((referencing.clone(), record, field.name.clone())),
+                    }
+                }
+            }
+        }
+
+        Ok(plan)
+    }
+
+    /// The foreign keys pointing at the table `table_name`, with their tables.
+    fn foreign_keys(state: &mut State, dir: &Path, table_name: &str) -> Result<Vec<(String, Field)>, Error> {
+        let reference_type = format!("Ref<{}>", table_name);
+        let names: Vec<String> = state.catalog.iter().map(|entry| entry.name.clone()).collect();
+
+        let mut foreign_keys = Vec::new();
+
+        for other in names {
+            // A table whose files are missing has no records (see `check_catalog()`)
+            if !dir.join(format!("{}.tbl", other)).exists() {
+                continue;
+            }
+
+            let schema = Self::table(state, dir, &other)?.read_schema()?;
+
+            if !schema.references.iter().any(|name| name == table_name) {
+                continue;
+            }
+
+            for field in schema.fields {
+                if field.is_fk && field.type_ == reference_type {
+                    foreign_keys.push((other.clone(), field));
+                }
+            }
+        }
+
+        Ok(foreign_keys)
+    }
+
+    /// Replaces the reference in the field `field` of a record with the null reference.
+    fn set_null(state: &mut State, dir: &Path, table_name: &str, oid: OID, field: &str) -> Result<(), Error> {
+        let table = Self::table(state, dir, table_name)?;
+        let schema = table.read_schema()?;
+
+        let Some((record_start, record)) = table.get(oid)? else {
+            return Ok(());
+        };
+
+        let payload_start = record_start + RECORD_HEADER_SIZE as u64;
+        let record = RawRecord::parse(table_name, &schema.fields, oid, payload_start, record)?;
+
+        match record.with_value(field, &0i32.to_bytes()) {
+            Some(record) => table.update(oid, &record).map(|_| ()),
+            None => Ok(()),
+        }
+    }
+
+    /// The rows of the join table of a `RefSet` that belong to `owner`: the OID of each
+    /// row and of its target, in the order in which they were added.
+    ///
+    /// The join table is scanned once and its rows are kept grouped by owner, until a
+    /// transaction writes to it other than through `add_join_row()` and `remove_join_row()`.
+    pub(crate) fn join_rows(&self, join_table: &str, owner: OID) -> Result<Vec<(OID, OID)>, Error> {
+        let mut state = self.lock()?;
+
+        let rows = Self::take_join_rows(&mut state, &self.inner.dir, join_table)?;
+        let owned = rows.get(&owner.val).cloned().unwrap_or_default();
+        state.join_rows.insert(join_table.to_string(), rows);
+
+        Ok(owned)
+    }
+
+    /// Adds a row to the join table of a `RefSet`, unless `owner` already has one for `target`.
+    ///
+    /// Returns `Ok(false)` if it already has one, `Error::NotFound` if there is no object
+    /// `target` in `target_table`.
+    pub(crate) fn add_join_row(&self, join_table: &str, owner: OID, target_table: &str, target: OID) -> Result<bool, Error> {
+        let mut state = self.lock()?;
+        let dir = &self.inner.dir;
+
+        if Self::table(&mut state, dir, target_table)?.get(target)?.is_none() {
+            return Err(Error::NotFound(format!("Object {} of {}", target.val, target_table)));
+        }
+
+        let mut rows = Self::take_join_rows(&mut state, dir, join_table)?;
+        let owned = rows.entry(owner.val).or_default();
+
+        if !owned.iter().any(|(_, oid)| *oid == target) {
+            let mut record = Vec::new();
+            for val in [0, owner.val, target.val] {
+                let value = val.to_bytes();
+                record.push(value.len() as u8);
+                record.extend_from_slice(&value);
+            }
+
+            // The commit forgets the rows of the table, they're given back once they're up to date
+            let row = Self::transaction(&mut state, dir, |state| Self::table(state, dir, join_table)?.insert(&record))?;
+            owned.push((row, target));
+            state.join_rows.insert(join_table.to_string(), rows);
+
+            return Ok(true);
+        }
+
+        state.join_rows.insert(join_table.to_string(), rows);
+        Ok(false)
+    }
+
+    /// Deletes the row of `owner` and `target` from the join table of a `RefSet`.
+    ///
+    /// Returns `Ok(false)` if there is no such row.
+    pub(crate) fn remove_join_row(&self, join_table: &str, owner: OID, target: OID) -> Result<bool, Error> {
+        let mut state = self.lock()?;
+        let dir = &self.inner.dir;
+
+        let mut rows = Self::take_join_rows(&mut state, dir, join_table)?;
+        let owned = rows.entry(owner.val).or_default();
+
+        if let Some(position) = owned.iter().position(|(_, oid)| *oid == target) {
+            let (row, _) = owned[position];
+
+            // Nothing refers to the rows of a join table, so there is nothing to cascade
+            Self::transaction(&mut state, dir, |state| Self::table(state, dir, join_table)?.delete(row))?;
+            owned.remove(position);
+            state.join_rows.insert(join_table.to_string(), rows);
+
+            return Ok(true);
+        }
+
+        state.join_rows.insert(join_table.to_string(), rows);
+        Ok(false)
+    }
+
+    /// Takes the rows of a join table out of `state.join_rows`, scanning the table if
+    /// they're not there.
+    fn take_join_rows(state: &mut State, dir: &Path, join_table: &str) -> Result<JoinRows, Error> {
+        if let Some(rows) = state.join_rows.remove(join_table) {
+            return Ok(rows);
+        }
+
+        let table = Self::table(state, dir, join_table)?;
+        let schema = table.read_schema()?;
+        let mut scan = RawScan::open(table)?;
+
+        let mut rows = JoinRows::new();
+        while let Some((oid, record_start, record)) = scan.next_raw()? {
+            let payload_start = record_start + RECORD_HEADER_SIZE as u64;
+            let record = RawRecord::parse(join_table, &schema.fields, oid, payload_start, record)?;
+
+            rows.entry(record.get("owner")?).or_default().push((oid, OID { val: record.get("target")? }));
+        }
+
+        Ok(rows)
+    }
+
+    /// Writes the header of the `.db` file followed by the catalog, with `num_of_tables`
+    /// set to the number of its entries.
+    ///
+    /// The new content is written to a copy of the file that is renamed over it, so a
+    /// crash leaves either the old catalog or the new one.
+    fn write_catalog(state: &mut State) -> Result<(), Error> {
+        let header = DbHeader { num_of_tables: state.catalog.len() as u8, ..state.header };
+
+        let mut content = header.to_bytes();
+        content.extend_from_slice(&file_manager::catalog_bytes(&state.catalog)?);
+
+        let new_path = state.db_path.with_extension("db.new");
+
+        let written = File::create(&new_path)
+            .and_then(|mut file| { file.write_all(&content)?; file.sync_all() });
+
+        if let Err(e) = written {
+            let _ = fs::remove_file(&new_path);
+            return Err(Error::Io(e));
+        }
+
+        fs::rename(&new_path, &state.db_path)?;
+        state.header = header;
+
+        Ok(())
+    }
+
+    /// Updates the schema hash of a table in the catalog, after its schema was changed.
+    fn update_catalog(state: &mut State, schema: &TableSchema) -> Result<(), Error> {
+        if let Some(entry) = state.catalog.iter_mut().find(|entry| entry.name == schema.name) {
+            entry.schema_hash = schema.schema_hash();
+        }
+
+        Self::write_catalog(state)
+    }
+
+    /// The other tables with `table_name` in their references section.
+    fn referencing_tables(state: &mut State, dir: &Path, table_name: &str) -> Result<Vec<String>, Error> {
+        let mut referencing = Vec::new();
+
+        let names: Vec<String> = state.catalog.iter().map(|entry| entry.name.clone()).collect();
+
+        for other in names {
+            if other != table_name
+                && dir.join(format!("{}.tbl", other)).exists()
+                && Self::table(state, dir, &other)?.read_schema()?.references.iter().any(|name| name == table_name)
+            {
+                referencing.push(other);
+            }
+        }
+
+        Ok(referencing)
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, State>, Error> {
+        let state = self.inner.state.lock()
+            .map_err(|_| Error::Poisoned(self.inner.name.clone()))?;
+
+        if state.broken {
+            return Err(Error::Poisoned(self.inner.name.clone()));
+        }
+
+        Ok(state)
+    }
+
+    /// Runs `f` on the files of a table, opening them the first time.
+    fn with_table<R>(&self, table_name: &str, f: impl FnOnce(&mut TableFiles) -> Result<R, Error>) -> Result<R, Error> {
+        let mut state = self.lock()?;
+
+        f(Self::table(&mut state, &self.inner.dir, table_name)?)
+    }
+
+    /// Runs `f` on the files of a table as a transaction, see `transaction()`.
+    fn write_table<R>(&self, table_name: &str, f: impl FnOnce(&mut TableFiles) -> Result<R, Error>) -> Result<R, Error> {
+        let mut state = self.lock()?;
+        let dir = &self.inner.dir;
+
+        Self::transaction(&mut state, dir, |state| f(Self::table(state, dir, table_name)?))
+    }
+
+    /// Runs `f`, then commits the writes it left pending in the tables.
+    ///
+    /// If `f` fails its writes are discarded, so the tables are left as they were.
+    fn transaction<R>(state: &mut State, dir: &Path, f: impl FnOnce(&mut State) -> Result<R, Error>) -> Result<R, Error> {
+        match f(state) {
+            Ok(result) => Self::commit(state, dir).map(|_| result),
+            Err(e) => {
+                for table in state.tables.values_mut() {
+                    table.discard_pending();
+                }
+
+                Err(e)
+            },
+        }
+    }
+
+    /// Commits the pending writes of the tables: they're appended to the log as one
+    /// transaction and flushed, then applied to the files of the tables, which are
+    /// flushed too, and finally the log is emptied.
+    ///
+    /// A crash at any point leaves either none or all of the writes in the tables,
+    /// once the log is recovered by `open()`. An error does the same without reopening:
+    /// - if the transaction can't be appended to the log, the log is cut back to where
+    ///   it was and nothing is applied;
+    /// - if it can't be applied, it's applied again from the log with
+    ///   `LogManager::recover()`.
+    ///
+    /// If the log can't be cut back or recovered, the handle is marked as broken and
+    /// its operations fail with `Error::Poisoned`: the log is left as it is, so that
+    /// the transaction is recovered when the database is opened again.
+    fn commit(state: &mut State, dir: &Path) -> Result<(), Error> {
+        let tables: Vec<(String, Vec<PageWrite>)> = state.tables.iter_mut()
+            .map(|(name, table)| (name.clone(), table.take_pending()))
+            .filter(|(_, writes)| !writes.is_empty())
+            .collect();
+
+        if tables.is_empty() {
+            return Ok(());
+        }
+
+        for (table_name, _) in &tables {
+            state.join_rows.remove(table_name);
+        }
+
+        let log_end = state.log.len()?;
+
+        if let Err(e) = state.log.append(&tables) {
+            // A partial transaction would stop the recovery before the ones appended after it
+            if state.log.truncate(log_end).is_err() {
+                state.broken = true;
+            }
+            return Err(e);
+        }
+
+        let applied = tables.iter().try_for_each(|(table_name, writes)| {
+            let table = state.tables.get_mut(table_name).unwrap();
+
+            table.apply(writes)?;
+            table.sync()
+        });
+
+        if let Err(e) = applied {
+            // The next checkpoint would drop the only copy of the transaction
+            return match state.log.recover(dir) {
+                Ok(_) => Ok(()),
+                Err(_) => {
+                    state.broken = true;
+                    Err(e)
+                },
+            };
+        }
+
+        state.log.checkpoint()
+    }
+
+    /// The files of a table, opened the first time they're used.
+    fn table<'a>(state: &'a mut State, dir: &Path, table_name: &str) -> Result<&'a mut TableFiles, Error> {
+        if !state.tables.contains_key(table_name) {
+            let table = TableFiles::open(dir, table_name)?;
+            state.tables.insert(table_name.to_string(), table);
+        }
+
+        Ok(state.tables.get_mut(table_name).unwrap())
+    }
+}
+
+/// What `Database::delete_record()` has to do, found before anything is changed.
+#[derive(Default)]
+struct DeletePlan {
+    /// The records to delete: the one passed to `delete_record()` and its cascades
+    delete: Vec<(String, OID)>,
+    /// The records of `delete`, to look them up
+    deleted: HashSet<(String, OID)>,
+    /// The references to set to null: table, record and field
+    set_null: Vec<(String, OID, String)>,
+    /// The records that restrict the deletion: table, record, field and the record they refer to
+    restrict: Vec<(String, OID, String, String, OID)>,
+}
+
+impl DeletePlan {
+    /// Adds a record to the ones to delete, returns `false` if it was already there.
+    fn add(&mut self, table_name: &str, oid: OID) -> bool {
+        if !self.deleted.insert((table_name.to_string(), oid)) {
+            return false;
+        }
+
+        self.delete.push((table_name.to_string(), oid));
+        true
+    }
+}
+
+/// The records of a table by the value of each of their foreign keys: the name of the
+/// field and the OID it refers to.
+type Referrers = HashMap<(String, i32), Vec<OID>>;
+
+/// Reads the foreign keys of the live records of a table, with one scan.
+///
+/// The records written before a foreign key was added don't have it, so they don't
+/// refer to anything through it.
+fn scan_referrers(table: &mut TableFiles) -> Result<Referrers, Error> {
+    let schema = table.read_schema()?;
+    let mut scan = RawScan::open(table)?;
+
+    let mut referrers = Referrers::new();
+    while let Some((oid, record_start, record)) = scan.next_raw()? {
+        let payload_start = record_start + RECORD_HEADER_SIZE as u64;
+        let record = RawRecord::parse(&table.name, &schema.fields, oid, payload_start, record)?;
+
+        for field in schema.fields.iter().filter(|field| field.is_fk) {
+            if record.bytes(&field.name).is_some() {
+                referrers.entry((field.name.clone(), record.get(&field.name)?)).or_default().push(oid);
+            }
+        }
+    }
+
+    Ok(referrers)
+}
+
+/// `schema` with the table `old` renamed to `new` in its references section and in
+/// the types of its `Ref` fields.
+fn retarget_references(schema: TableSchema, old: &str, new: &str) -> TableSchema {
+    let old_type = format!("Ref<{}>", old);
+
+    TableSchema {
+        references: schema.references.into_iter()
+            .map(|name| if name == old { new.to_string() } else { name })
+            .collect(),
+        fields: schema.fields.into_iter()
+            .map(|field| if field.is_fk && field.type_ == old_type {
+                Field { type_: format!("Ref<{}>", new), ..field }
+            } else {
+                field
+            })
+            .collect(),
+        ..schema
+    }
+}
+
+/// The suffix of the file that marks a migration whose new files are complete.
+const MIGRATION_MARKER: &str = ".migration.ready";
+
+/// The suffix of the file that marks a rename, containing the new name padded to 64 bytes.
+const RENAME_MARKER: &str = ".rename";
+
+/// The suffix of the file that marks the drop of a table.
+const DROP_MARKER: &str = ".drop";
+
+/// The marker of a change to the table `table_name` that `Database::open()` finishes if
+/// it was interrupted, see `Database::finish_changes()`.
+fn marker(dir: &Path, table_name: &str, suffix: &str) -> PathBuf {
+    dir.join(format!("{}{}", table_name, suffix))
+}
+
+/// Writes a copy of `table` with the schema of `T` at the given paths, converting
+/// each of its records with `migration` (see `Database::migrate()`).
+fn copy_migrated<T, F>(
+    table: &mut TableFiles,
+    schema: &TableSchema,
+    tbl_path: &Path,
+    bucket_path: &Path,
+    migration: &mut F
+) -> Result<(), Error>
+where
+    T: Objekt,
+    F: FnMut(RawRecord) -> Result<T, Error>,
+{
+    let fields = T::get_fields();
+
+    // The Ref fields added by the migration are references too, see `foreign_keys()`
+    let mut references: Vec<String> = Vec::new();
+    for target in fields.iter().filter_map(|field| field.ref_target()) {
+        if !references.iter().any(|name| name == target) {
+            references.push(target.to_string());
+        }
+    }
+
+    let new_schema = TableSchema {
+        last_oid: OID { val: 0 },
+        version: schema.version.saturating_add(1),
+        references,
+        fields,
+        ..schema.clone()
+    };
+    let tbl_file = file_manager::table_file_bytes(&new_schema)?;
+
+    fs::write(tbl_path, tbl_file)?;
+    File::create(bucket_path)?;
+
+    let mut new_table = TableFiles::open_paths(&table.name, tbl_path.to_path_buf(), bucket_path)?;
+    let mut scan = RawScan::open(table)?;
+
+    while let Some((oid, record_start, record)) = scan.next_raw()? {
+        let payload_start = record_start + RECORD_HEADER_SIZE as u64;
+        let raw = RawRecord::parse(&table.name, &schema.fields, oid, payload_start, record)?;
+
+        new_table.insert_as(oid.val as u32, &migration(raw)?.to_bytes()?)?;
+        new_table.apply_pending()?;
+    }
+
+    new_table.set_last_oid(schema.last_oid.val as u32)?;
+    new_table.apply_pending()?;
+    new_table.sync()
+}
+
+/// The names of the tables of the database in `dir`, one for each `.tbl` file.
+fn table_names(dir: &Path) -> Result<Vec<String>, Error> {
+    let mut names = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_file() && path.extension().is_some_and(|extension| extension == "tbl")
+            && let Some(name) = path.file_stem().and_then(|name| name.to_str())
+        {
+            names.push(name.to_string());
+        }
+    }
+
+    Ok(names)
+}
+
+fn remove_if_exists(path: &Path) -> Result<(), Error> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(Error::Io(e)),
+        _ => Ok(()),
+    }
+}
+
+/// Locks `OPEN_DATABASES`.
+///
+/// A panic while it was locked can't leave the list half updated, so a poisoned lock is just taken over.
+fn open_databases() -> MutexGuard<'static, Vec<(PathBuf, Weak<Inner>)>> {
+    OPEN_DATABASES.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The handle registered in `open` under `key`, if it's still alive.
+fn live_handle(open: &[(PathBuf, Weak<Inner>)], key: &Path) -> Option<Arc<Inner>> {
+    open.iter()
+        .filter(|(dir, _)| dir == key)
+        .find_map(|(_, inner)| inner.upgrade())
+}
+
+/// The same database is registered under the same key whatever path was used to open it.
+fn registry_key(dir: &Path) -> PathBuf {
+    fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf())
+}
+
+/// The name of a database is the name of its directory.
+fn db_name(dir: &Path) -> Result<String, Error> {
+    dir.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.to_string())
+        .ok_or_else(|| Error::InvalidPath(dir.to_path_buf()))
+}
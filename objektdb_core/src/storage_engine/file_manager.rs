@@ -1,8 +1,12 @@
-use std::fs::{File, self, OpenOptions};
-use std::io::{Read, Write};
-use std::path::{Path};
-use crate::support_mods::support_functions;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use crate::traits::objekt::Objekt;
 
+use super::database::Database;
+use super::log_manager::{PageWrite, TableFile};
+use crate::error::Error;
 use super::super::support_mods::{field::*, support_functions::*};
 use std::{env};
 
@@ -22,17 +26,106 @@ use std::{env};
 /// - Rejects files that do not match the expected format.
 pub const MAGIC_NUMBER: u32 = 0x4D594442;
 
+/// Size in bytes of the part of a `.tbl` header that doesn't depend on the schema:
+/// the table name (64 bytes), `offset_header` (4 bytes), `last_OID` (3 bytes),
+/// `schema_version` (2 bytes), `references_num` (1 byte) and the length of the fields (2 bytes).
+pub const TBL_FIXED_HEADER_SIZE: usize = 76;
+
+/// Bytes left empty at the end of a `.tbl` header, so that fields can be added
+/// to the schema without moving the index and the data section.
+pub const SCHEMA_RESERVE: usize = 256;
+
+/// Subdirectory of a database where `reinitialize_table()` keeps the old files of the tables.
+pub const BACKUP_DIR: &str = "backups";
+
+/// Bit of the flags byte of a field set if the field is a foreign key.
+pub const FIELD_FK: u8 = 0b01;
+
+/// Bit of the flags byte of a field set if the field has a default value, so
+/// that it can be missing at the end of the records written before it was added.
+pub const FIELD_DEFAULT: u8 = 0b10;
+
+/// Bit of the flags byte of a foreign key set if the record is deleted with the object
+/// it refers to (`OnDelete::Cascade`).
+pub const FIELD_ON_DELETE_CASCADE: u8 = 0b100;
+
+/// Bit of the flags byte of a foreign key set if the reference is set to null when the
+/// object it refers to is deleted (`OnDelete::SetNull`). Without this bit and
+/// `FIELD_ON_DELETE_CASCADE` the deletion is restricted.
+pub const FIELD_ON_DELETE_SET_NULL: u8 = 0b1000;
+
+/// Size in bytes of the header of a `.db` file.
+pub const DB_HEADER_SIZE: usize = 10;
+
+/// Version of the file format written by this build, stored in the `version` byte of the `.db` header.
+///
+/// Files with any other version are refused by `Database::open()` with `Error::UnsupportedVersion`.
+/// Version 1 is the layout of the `.tbl` files before the record headers and the field flags,
+/// that this build can't read.
+pub const DB_VERSION: u8 = 2;
+
+/// The bits of the `flags` field of the `.db` header understood by this build.
+///
+/// Each bit of `flags` enables a feature of the file format. A file with a bit that is
+/// not in this mask uses a feature this build doesn't know, so `Database::open()` refuses
+/// it with `Error::UnsupportedFlags` instead of misreading it.
+pub const SUPPORTED_FLAGS: u32 = FLAG_CATALOG;
+
+/// Bit of the `flags` field of the `.db` header set if the header is followed by the
+/// catalog of the tables (see `TableEntry`).
+///
+/// Every database of version `DB_VERSION` has it: a header without it is refused with
+/// `Error::CorruptCatalog`.
+pub const FLAG_CATALOG: u32 = 0b1;
+
+/// Size in bytes of the index section of a `.tbl` file.
+///
+/// The OID is reduced to 16 bits by `fx_hash16()`, so the index has 2^16 slots
+/// of 4 bytes each: 2^16 * 4 = 262,144 bytes (256 KB). Each slot holds the number
+/// of the first node of its chain in the `_bucket.bin` file (0 if the slot is empty).
+pub const INDEX_SIZE: usize = 262144;
+
+/// Size in bytes of a node in the `_bucket.bin` file.
+///
+/// - OID (3 bytes, little-endian)
+/// - Address of the record, relative to the beginning of the data section (4 bytes, little-endian)
+/// - Number of the next node in the chain (3 bytes, little-endian, 0 if it's the last one)
+///
+/// Nodes are numbered starting from 1, in the order in which they are written.
+pub const BUCKET_NODE_SIZE: usize = 10;
+
+/// Size in bytes of the header written before every record of the data section.
+///
+/// - Status (1 byte): `RECORD_LIVE` for a valid record, `RECORD_DELETED` for a deleted one
+/// - OID (3 bytes, little-endian)
+/// - Capacity (4 bytes, little-endian): bytes reserved for the record after its header
+/// - Length (4 bytes, little-endian): bytes actually used by the record, never more than the capacity
+pub const RECORD_HEADER_SIZE: usize = 12;
+
+/// Status byte of a record that can be read.
+pub const RECORD_LIVE: u8 = 1;
+
+/// Status byte of a record that has been deleted (tombstone).
+pub const RECORD_DELETED: u8 = 0;
+
+/// The biggest OID that fits in the 3 bytes reserved for it.
+pub const MAX_OID: u32 = 0xFF_FFFF;
+
 /// Creates a new database file and its directory structure.
 ///
 /// This function initializes a new database by creating a directory named after `db_name`
 /// and a corresponding `.db` file inside it. The database file is initialized with a binary
 /// header containing metadata required for future operations.
 ///
-/// The header format is as follows (total 10 bytes):
+/// The header format is as follows (`DB_HEADER_SIZE`, 10 bytes):
 /// - Magic number (4 bytes, little-endian): Identifies the file as a valid objektDB database.
-/// - Version (1 byte): Database format version.
+/// - Version (1 byte): Database format version, `DB_VERSION`.
 /// - Number of tables (1 byte): Initially set to 0.
-/// - Flags (4 bytes): Reserved for future use.
+/// - Flags (4 bytes, little-endian): The features of the file format used by the file (see
+///   `SUPPORTED_FLAGS`). A new database has `FLAG_CATALOG` set.
+///
+/// The header is followed by the catalog, with an entry for each table (see `TableEntry`):
+/// it's empty when the database is created.
 ///
 /// # Arguments
 ///
@@ -42,7 +135,7 @@ pub const MAGIC_NUMBER: u32 = 0x4D594442;
 /// # Returns
 ///
 /// * `Ok(())` if the database directory and file were successfully created and initialized.
-/// * `Err(String)` if the directory or file could not be created, or if the database already exists.
+/// * `Err(Error)` if the directory or file could not be created, or if the database already exists.
 ///
 /// # Errors
 ///
@@ -63,71 +156,49 @@ pub const MAGIC_NUMBER: u32 = 0x4D594442;
 ///
 /// # Notes
 /// - This function does not create any tables; use `create_table()` to add tables after database creation.
-/// - The database directory will be created in the current working directory.
+/// - The database directory will be created in the current working directory: use `Database::create()`
+///   to create it somewhere else and to get a handle to it.
 /// - If an error occurs after the directory is created but before the file is written, the directory may remain on disk.
-pub fn create_db(db_name: String) -> Result<(), String> {
+pub fn create_db(db_name: String) -> Result<(), Error> {
     //Work directory on developer dir
-    let current_dir = env::current_dir()
-        .map_err(|e| format!("Error getting current directory: {}", e))?;
-
-    let db_dir = current_dir.join(&db_name);
-
-    fs::create_dir(&db_dir)
-        .map_err(|e| format!("Error creating database directory: {}", e))?;
-
-    let db_file_path = db_dir.join(format!("{}.db", db_name));
-
-    if db_file_path.exists() {
-        return Err("The database already exists".to_string());
-    }
-
-    match File::create(&db_file_path) {
-        Err(e) => {
-            let _ = fs::remove_dir(&db_dir); // pulizia
-            Err(format!("Error creating database file: {}", e))
-        },
-        Ok(mut file) => {
-            let mut buffer = Vec::with_capacity(10);
+    let current_dir = env::current_dir()?;
 
-            buffer.extend_from_slice(&MAGIC_NUMBER.to_le_bytes()); // Magic number
-            buffer.extend_from_slice(&[1u8]); // Version
-            buffer.extend_from_slice(&[0u8]); // Number of tables
-            buffer.extend_from_slice(&[0u8; 4]); // Flags
-
-            file.write_all(&buffer)
-                .map_err(|e| format!("Failed to write database header: {}", e))
-        }
-    }
+    Database::create(current_dir.join(&db_name)).map(|_| ())
 }
 
 /// Creates a new table within an existing objektDB database.
-
 ///
 /// This function appends a new table to an existing database file (`.db`)
 /// and creates a corresponding `.tbl` file containing the table's metadata and schema.
 ///
 /// The database file is updated as follows:
-/// - Byte 4 of the `.db` file is incremented to reflect the number of tables (max 255).
+/// - Byte 5 of the `.db` file (`num_of_tables`) is incremented to reflect the number of tables (max 255).
 ///
 /// The `.tbl` file is structured as follows:
 /// - Table name: 64 bytes, left-padded with null bytes (`\0`)
-/// - Offset to data section: 4 bytes, little-endian `u32`
-/// - Reserved: 3 bytes (currently unused)
-/// - References: 
+/// - Offset header: 4 bytes, little-endian `u32`, where the header ends and the index begins
+/// - Last OID: 3 bytes, little-endian, the last object id assigned (0 while the table is empty)
+/// - Schema version: 2 bytes, little-endian, 1 when the table is created and incremented
+///   every time its fields change
+/// - References:
 ///   - 1 byte for the number of references
-///   - Each reference name: 64 bytes (left null-padded)
+///   - Each reference name: 64 bytes (left null-padded), the name of a referenced table
 /// - Fields:
+///   - Length of the fields: 2 bytes, little-endian, where the fields end and the methods begin
 ///   - For each field:
 ///     - Name length (1 byte)
-///     - Name (variable)
-///     - is_FK flag (1 byte)
+///     - Name (variable), the identifier of the field in the struct
+///     - Flags (1 byte): `FIELD_FK` if it's a foreign key, `FIELD_DEFAULT` if it has a default value,
+///       `FIELD_ON_DELETE_CASCADE` or `FIELD_ON_DELETE_SET_NULL` for its `on_delete` policy
 ///     - Type length (1 byte)
-///     - Type name (variable)
+///     - Type name (variable), `OID` or the `T` of `Primitive<T>`
 /// - Methods:
 ///   - For each method:
 ///     - Name length (1 byte)
 ///     - Name (variable)
-/// - Data section: pre-allocated space (256 KB) reserved for future data and index
+/// - Reserved: `SCHEMA_RESERVE` zero bytes, where the fields added later are written
+/// - Index section: pre-allocated space (256 KB), one 4-byte slot for each 16-bit hash
+/// - Data section: empty, records are appended to it by `insert_record()`
 ///
 /// # Arguments
 ///
@@ -140,7 +211,7 @@ pub fn create_db(db_name: String) -> Result<(), String> {
 /// # Returns
 ///
 /// * `Ok(())` on success.
-/// * `Err(String)` if the table could not be created due to I/O issues, table name length,
+/// * `Err(Error)` if the table could not be created due to I/O issues, table name length,
 ///   or invalid database file format.
 ///
 /// # Errors
@@ -156,13 +227,19 @@ pub fn create_db(db_name: String) -> Result<(), String> {
 /// let fields = vec![
 ///     Field {
 ///         name: "id".to_string(),
-///         is_FK: false,
-///         type_: "int".to_string(),
+///         is_oid: true,
+///         is_fk: false,
+///         has_default: false,
+///         on_delete: OnDelete::Restrict,
+///         type_: "OID".to_string(),
 ///     },
 ///     Field {
 ///         name: "name".to_string(),
-///         is_FK: false,
-///         type_: "string".to_string(),
+///         is_oid: false,
+///         is_fk: false,
+///         has_default: false,
+///         on_delete: OnDelete::Restrict,
+///         type_: "String".to_string(),
 ///     },
 /// ];
 ///
@@ -177,177 +254,1047 @@ pub fn create_db(db_name: String) -> Result<(), String> {
 /// ```
 ///
 /// # Notes
-/// - The function assumes the database has been initialized using `create_db`.
+/// - The function assumes the database has been initialized using `create_db`, in the current working directory.
+///   It's a shortcut for `Database::open()` followed by `Database::create_table()`.
 /// - `.tbl` files are created inside the same directory as the database.
 pub fn create_table(
     _table_name: String, 
     _db_name: String,
+    _ref: Vec<String>,
     _fields: Vec<Field>, 
     _methods_names: Vec<String>
-) -> Result<(), String> {
+) -> Result<(), Error> {
     
-    let current_dir = env::current_dir()
-        .map_err(|e| format!("Error getting current directory: {}", e))?;
+    let current_dir = env::current_dir()?;
+
+    Database::open(current_dir.join(&_db_name))?
+        .create_table(_table_name, _ref, _fields, _methods_names)
+}
 
+/// Deletes the specified database, with all its tables.
+///
+/// This function removes the directory of the database, with the `.db` file, the `.tbl`
+/// and `_bucket.bin` files of each table and the backups made by `reinitialize_table()`.
+///
+/// # Arguments
+///
+/// * `db_name` - The name of the database (without the `.db` extension) to delete.
+///
+/// # Returns
+///
+/// * `Ok(())` if the database was successfully deleted.
+/// * `Err(Error)` if the database does not exist, if a `Database` handle to it is still open
+///   (`Error::InUse`) or if an error occurred during deletion.
+///
+/// # Example
+///
+/// ```ignore
+/// use objektDB::storage_engine::file_manager::delete_db;
+///
+/// match delete_db(String::from("my_database")) {
+///     Ok(_) => println!("Database deleted successfully!"),
+///     Err(e) => println!("Error deleting database: {}", e),
+/// }
+/// ```
+///
+/// # Notes
+/// - Like `create_db()`, it looks for the database in the current working directory:
+///   use `Database::delete()` to delete it somewhere else.
+pub fn delete_db(db_name: String) -> Result<(), Error> {
+    let current_dir = env::current_dir()?;
 
-    //CHANGES TO DB FILE
-    let path = current_dir.join(format!("{}/{}.db", _db_name, _db_name));
+    Database::delete(current_dir.join(&db_name))
+}
 
-    // Check if the database file exists
-    if Path::new(&path).exists() {
 
-        let mut file: File = OpenOptions::new()
-                                .read(true)
-                                .write(true)
-                                .open(&path)
-                                .map_err(|e| format!("Error opening database file: {}", e))?;
-        
-        let mut buffer: Vec<u8> = Vec::new();
+///Allows you to reinitialize a table.
+///
+///It must be called **manually** by the developer 
+///when applying some change to the structure of one 
+///of the structs. The method clears the table and reinitializes it
+///with the new references and fields, keeping its methods.
+///
+///The old files of the table are kept in `<db_name>/backups/<table_name>-<timestamp>/`
+///(see `Database::reinitialize_table()`), so they can be restored by hand.
+///
+///**Caution**: the method deletes all data within the table.
+///To keep the data, convert the records to the new struct with
+///`Database::migrate()` instead.
+///
+/// # Notes
+/// - Like `create_table()`, it looks for the database in the current working directory.
+pub fn reinitialize_table(_table_name: String, _db_name: String, _ref: Vec<String>, _fields: Vec<Field>)-> Result<(), Error>{
+    let current_dir = env::current_dir()?;
+    let db = Database::open(current_dir.join(&_db_name))?;
 
-        file.read_to_end(&mut buffer)
-            .map_err(|e| format!("Error reading database file: {}", e))?;
+    let methods = db.read_schema(&_table_name)?.methods;
 
-        if buffer[0..4] == MAGIC_NUMBER.to_le_bytes() {
-            if buffer[4] >= 255 {
-                return Err("Maximum number of tables reached (255)".to_string());
-            }
+    db.reinitialize_table(_table_name, _ref, _fields, methods).map(|_| ())
+}
 
 
-            buffer[4] += 1; // Increment the number of tables
+/// The header of a `.db` file.
+///
+/// See `create_db()` for its layout on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DbHeader {
+    pub version: u8,
+    pub num_of_tables: u8,
+    pub flags: u32,
+}
 
-            let _ = file.write(&buffer);
-            
-        }else{
-            return Err("Invalid database file format".to_string());
+impl DbHeader {
+    /// The header of a new, empty database.
+    pub(crate) fn new() -> Self {
+        DbHeader {
+            version: DB_VERSION,
+            num_of_tables: 0,
+            flags: FLAG_CATALOG,
         }
-    }else{
-        return Err(format!("Database {} does not exist", _db_name));
     }
 
+    /// Parses the first `DB_HEADER_SIZE` bytes of a `.db` file.
+    ///
+    /// The magic number, the version and the flags are checked, so a header returned
+    /// by this function can be used safely by this build and is followed by the catalog.
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < DB_HEADER_SIZE || bytes[0..4] != MAGIC_NUMBER.to_le_bytes() {
+            return Err(Error::InvalidMagic);
+        }
+
+        let header = DbHeader {
+            version: bytes[4],
+            num_of_tables: bytes[5],
+            flags: u32::from_le_bytes([bytes[6], bytes[7], bytes[8], bytes[9]]),
+        };
 
-    //CHANGES TO TBL FILE
+        if header.version != DB_VERSION {
+            return Err(Error::UnsupportedVersion(header.version));
+        }
 
+        let unknown_flags = header.flags & !SUPPORTED_FLAGS;
+        if unknown_flags != 0 {
+            return Err(Error::UnsupportedFlags(unknown_flags));
+        }
 
-    //we use null-padding left
-    let mut name_bytes: Vec<u8> = Vec::new();
-    if let Err(e) = string_padding(&mut name_bytes, _table_name.clone(), 64){
-        return Err("Table name is too long, must be 64 bytes or less".to_string());
+        if !header.has_flag(FLAG_CATALOG) {
+            return Err(Error::CorruptCatalog { offset: DB_HEADER_SIZE as u64 });
+        }
+
+        Ok(header)
     }
 
+    /// Whether all the feature bits of `flag` are set in the header.
+    pub fn has_flag(&self, flag: u32) -> bool {
+        self.flags & flag == flag
+    }
 
+    pub(crate) fn to_bytes(self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(DB_HEADER_SIZE);
 
-    let path = current_dir.join(format!("{}/{}.tbl",_db_name, _table_name));
+        buffer.extend_from_slice(&MAGIC_NUMBER.to_le_bytes()); // Magic number
+        buffer.push(self.version); // Version
+        buffer.push(self.num_of_tables); // Number of tables
+        buffer.extend_from_slice(&self.flags.to_le_bytes()); // Flags
 
+        buffer
+    }
+}
 
-    //support_functions::converter_builder(_ref)
-    //    .map_err(|e|format!("Error creating file for converting from strings to values: {}", e))?;
+/// An entry of the catalog of a database, describing one of its tables.
+///
+/// The catalog follows the header of the `.db` file, with one entry per table
+/// (`num_of_tables` entries):
+///
+/// | Bytes | Content |
+/// |---|---|
+/// | 4 | `id`, little-endian |
+/// | 64 | `name`, left-padded with zeros as in the `.tbl` header |
+/// | 8 | `schema_hash`, little-endian |
+/// | 1 + n | `file_name`, preceded by its length |
+///
+/// It's returned by `Database::tables()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableEntry {
+    /// Assigned when the table is created and never reused while the table exists.
+    pub id: u32,
+    pub name: String,
+    /// `TableSchema::schema_hash()` of the schema in the header of the `.tbl` file.
+    pub schema_hash: u64,
+    /// The name of the `.tbl` file in the database directory, the bucket is `<name>_bucket.bin`.
+    pub file_name: String,
+}
 
-    //length_field+field+is_fk+length_type+type
-    let mut fields: Vec<u8> = Vec::new();
+impl TableEntry {
+    pub(crate) fn new(id: u32, schema: &TableSchema) -> Self {
+        TableEntry {
+            id,
+            name: schema.name.clone(),
+            schema_hash: schema.schema_hash(),
+            file_name: format!("{}.tbl", schema.name),
+        }
+    }
+}
 
-    //length_fields
-    let mut tot_len: Vec<u8> = Vec::new();
+/// Encodes the catalog written after the header of the `.db` file.
+pub(crate) fn catalog_bytes(catalog: &[TableEntry]) -> Result<Vec<u8>, Error> {
+    let mut buffer = Vec::new();
+
+    for entry in catalog {
+        buffer.extend_from_slice(&entry.id.to_le_bytes());
+        string_padding(&mut buffer, entry.name.clone(), 64)?;
+        buffer.extend_from_slice(&entry.schema_hash.to_le_bytes());
+        push_short_str(&mut buffer, &entry.file_name)?;
+    }
+
+    Ok(buffer)
+}
 
-    for field in _fields{
+/// Parses the `num_of_tables` entries of the catalog, `bytes` being the `.db` file after its header.
+pub(crate) fn parse_catalog(bytes: &[u8], num_of_tables: u8) -> Result<Vec<TableEntry>, Error> {
+    let mut catalog = Vec::with_capacity(num_of_tables as usize);
+    let mut position = 0;
 
-        //name
-        fields.push(field.name.len() as u8);
-        fields.extend_from_slice(field.name.as_bytes());
+    // Offsets in the errors are positions in the `.db` file
+    let corrupt = |offset: usize| Error::CorruptCatalog { offset: (DB_HEADER_SIZE + offset) as u64 };
 
+    for _ in 0..num_of_tables {
+        let start = position;
+        let fixed = bytes.get(position..position + 4 + 64 + 8 + 1).ok_or_else(|| corrupt(start))?;
+
+        let id = u32::from_le_bytes(fixed[0..4].try_into().unwrap());
+        let name = std::str::from_utf8(&fixed[4..68])
+            .map_err(|_| corrupt(start + 4))?
+            .trim_start_matches('\0')
+            .to_string();
+        let schema_hash = u64::from_le_bytes(fixed[68..76].try_into().unwrap());
+        let len = fixed[76] as usize;
+        position += fixed.len();
+
+        let file_name = bytes.get(position..position + len)
+            .and_then(|name| std::str::from_utf8(name).ok())
+            .ok_or_else(|| corrupt(position))?
+            .to_string();
+        position += len;
+
+        catalog.push(TableEntry { id, name, schema_hash, file_name });
     }
 
-    tot_len.extend_from_slice(&(fields.len() as u16).to_le_bytes());
+    Ok(catalog)
+}
 
+/// Builds the content of a new `.tbl` file: the header and the empty index.
+///
+/// See `create_table()` for the layout.
+pub(crate) fn table_file_bytes(schema: &TableSchema) -> Result<Vec<u8>, Error> {
+    //header+index
+    Ok([table_header_bytes(schema, 0)?, vec![0u8; INDEX_SIZE]].concat())
+}
 
-    let mut methods: Vec<u8> = Vec::new();
+/// Builds the header of a `.tbl` file with the given schema.
+///
+/// The header is followed by zeros up to `min_size`, so that it can replace a header of that
+/// size without moving the index. If it doesn't fit in `min_size`, `SCHEMA_RESERVE` zeros are
+/// left after it instead, for the fields added later (see `Database::ensure_table()`).
+pub(crate) fn table_header_bytes(schema: &TableSchema, min_size: usize) -> Result<Vec<u8>, Error> {
+    //we use null-padding left
+    let mut name_bytes: Vec<u8> = Vec::new();
+    string_padding(&mut name_bytes, schema.name.clone(), 64)?;
+
+    //references_num+struct_name1+struct_name2...
+    let references_num = u8::try_from(schema.references.len())
+        .map_err(|_| Error::TableLimit)?;
+
+    let mut refs: Vec<u8> = vec![references_num];
+
+    for reference in &schema.references{
+        string_padding(&mut refs, reference.clone(), 64)?;
+    }
+
+    //length_field+field+flags+length_type+type
+    let mut fields: Vec<u8> = Vec::new();
 
-    for method in _methods_names{
-        methods.push(method.len() as u8);
-        methods.extend_from_slice(method.as_bytes());
+    for field in &schema.fields{
+        push_short_str(&mut fields, &field.name)?;
+        fields.push(field_flags(field));
+        push_short_str(&mut fields, &field.type_)?;
     }
 
-    let offset_header: [u8; 4] = ((76+fields.len()+methods.len()) as u32).to_le_bytes();
+    //length_fields
+    let tot_len = u16::try_from(fields.len())
+        .map_err(|_| Error::NameTooLong(format!("{} (fields)", schema.name)))?;
+
+    let mut methods: Vec<u8> = Vec::new();
 
-    let mut header: Vec<u8> = Vec::new();
+    for method in &schema.methods{
+        push_short_str(&mut methods, method)?;
+    }
+
+    let len = TBL_FIXED_HEADER_SIZE + (refs.len() - 1) + fields.len() + methods.len();
+    let offset_header = if len <= min_size { min_size } else { len + SCHEMA_RESERVE };
 
+    let mut header: Vec<u8> = Vec::with_capacity(offset_header);
 
     header.extend_from_slice(&name_bytes);
-    header.extend_from_slice(&offset_header);
-    header.extend_from_slice(&[0u8; 3]);
+    header.extend_from_slice(&(offset_header as u32).to_le_bytes());
+    header.extend_from_slice(&u24_to_le_bytes(schema.last_oid.val as u32));
+    header.extend_from_slice(&schema.version.to_le_bytes());
+    header.extend_from_slice(&refs);
+    header.extend_from_slice(&tot_len.to_le_bytes());
     header.extend_from_slice(&fields);
     header.extend_from_slice(&methods);
+    header.resize(offset_header, 0);
 
-    //header+index
-    let tbl_file = [header, vec![0u8; 262144 as usize]].concat();
-    match File::create(path){
-        Err(e)=> Err(format!("The table could not be created: {}", e)),
-        Ok(mut f)=>{
-
-            f.write(&tbl_file)
-                .map_err(
-                    |e|format!("Error creating the .tbl file: {}", e)
-                )?;
-            File::create(current_dir.join(format!("{}/{}_bucket.bin", _db_name, _table_name) ))
-                .map_err(|e| format!("Error creating the bucket file: {}", e))?;
-            return Ok(());
-        }
+    Ok(header)
+}
+
+/// The flags byte of a field in the `.tbl` header.
+fn field_flags(field: &Field) -> u8 {
+    let mut flags = 0;
+
+    if field.is_fk {
+        flags |= FIELD_FK;
+    }
+    if field.has_default {
+        flags |= FIELD_DEFAULT;
+    }
+    match field.on_delete {
+        OnDelete::Restrict => {},
+        OnDelete::Cascade => flags |= FIELD_ON_DELETE_CASCADE,
+        OnDelete::SetNull => flags |= FIELD_ON_DELETE_SET_NULL,
     }
+
+    flags
 }
 
-/// Deletes the specified database file from the filesystem.
-///
-/// This function attempts to remove the database file with the given name.
-/// The database file is expected to have a `.db` extension. If the file exists,
-/// it will be deleted. If the file does not exist, an error is returned.
-///
-/// # Arguments
-///
-/// * `db_name` - The name of the database (without the `.db` extension) to delete.
+/// Parses the header of a `.tbl` file, see `create_table()` for its layout.
 ///
-/// # Returns
-///
-/// * `Ok(())` if the database file was successfully deleted.
-/// * `Err(String)` if the file does not exist or if an error occurred during deletion.
-///
-/// # Example
+/// `bytes` must contain the whole header, up to `offset_header`.
+pub(crate) fn parse_table_header(table_name: &str, bytes: &[u8]) -> Result<TableSchema, Error> {
+    let mut reader = HeaderReader { table_name, bytes, position: 0 };
+
+    let name = reader.padded_name()?;
+
+    let offset_header = u32::from_le_bytes(reader.take(4)?.try_into().unwrap()) as usize;
+    if offset_header != bytes.len() {
+        return Err(reader.corrupt_at(64));
+    }
+
+    let last_oid = OID { val: read_u24(reader.take(3)?) as i32 };
+
+    let version = u16::from_le_bytes(reader.take(2)?.try_into().unwrap());
+
+    let references_num = reader.take(1)?[0];
+    let mut references = Vec::with_capacity(references_num as usize);
+    for _ in 0..references_num {
+        references.push(reader.padded_name()?);
+    }
+
+    let fields_len = u16::from_le_bytes(reader.take(2)?.try_into().unwrap()) as usize;
+    let fields_end = reader.position + fields_len;
+
+    let mut fields = Vec::new();
+    while reader.position < fields_end {
+        let name = reader.short_str()?;
+        let flags = reader.take(1)?[0];
+        let type_ = reader.short_str()?;
+
+        fields.push(Field {
+            is_oid: type_ == "OID",
+            name,
+            is_fk: flags & FIELD_FK != 0,
+            has_default: flags & FIELD_DEFAULT != 0,
+            on_delete: if flags & FIELD_ON_DELETE_CASCADE != 0 {
+                OnDelete::Cascade
+            } else if flags & FIELD_ON_DELETE_SET_NULL != 0 {
+                OnDelete::SetNull
+            } else {
+                OnDelete::Restrict
+            },
+            type_,
+        });
+    }
+
+    if reader.position != fields_end {
+        return Err(reader.corrupt_at(fields_end));
+    }
+
+    // The methods end where the zeros reserved for the schema begin
+    let mut methods = Vec::new();
+    while reader.position < bytes.len() && bytes[reader.position] != 0 {
+        methods.push(reader.short_str()?);
+    }
+
+    Ok(TableSchema { name, last_oid, version, references, fields, methods })
+}
+
+/// Reads the header of a `.tbl` file in order, failing with `Error::Corrupt` on invalid data.
+struct HeaderReader<'a> {
+    table_name: &'a str,
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> HeaderReader<'a> {
+    fn corrupt_at(&self, offset: usize) -> Error {
+        Error::Corrupt { table: self.table_name.to_string(), offset: offset as u64 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let end = self.position + len;
+        let bytes = self.bytes.get(self.position..end)
+            .ok_or_else(|| self.corrupt_at(self.position))?;
+
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn string(&self, bytes: &[u8], offset: usize) -> Result<String, Error> {
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| self.corrupt_at(offset))
+    }
+
+    /// A name of 64 bytes, left null-padded (see `string_padding()`).
+    fn padded_name(&mut self) -> Result<String, Error> {
+        let start = self.position;
+        let bytes = self.take(64)?;
+        let name_start = bytes.iter().position(|&b| b != 0).unwrap_or(64);
+
+        self.string(&bytes[name_start..], start)
+    }
+
+    /// A string preceded by its length in 1 byte (see `push_short_str()`).
+    fn short_str(&mut self) -> Result<String, Error> {
+        let start = self.position;
+        let len = self.take(1)?[0] as usize;
+        let bytes = self.take(len)
+            .map_err(|_| self.corrupt_at(start))?;
+
+        self.string(bytes, start)
+    }
+}
+
+/// The files of a table opened by a `Database`.
 ///
-/// ```ignore
-/// use objektDB::storage_engine::file_manager::delete_db;
+/// It implements the operations on the index, the bucket and the data section.
+/// Every method seeks before reading or writing, so the position of the files
+/// doesn't matter between calls.
 ///
-/// match delete_db(String::from("my_database")) {
-///     Ok(_) => println!("Database deleted successfully!"),
-///     Err(e) => println!("Error deleting database: {}", e),
-/// }
-/// ```
-/// 
-/// NOT COMPLETE
-pub fn delete_db(db_name: String) -> Result<(), String> {
-    let db_name = db_name;
-    let db_path = format!("{}/{}.db", db_name, db_name);
-    
-    if Path::new(&db_path).exists() {
-        std::fs::remove_file(&db_path).map_err(|e| format!("Error deleting database: {}", e))?;
+/// The writes of these operations are not applied to the files: they're kept in
+/// memory until the `Database` logs them and applies them with `apply_pending()`
+/// (see `LogManager`). The reads see the pending writes, as if they were applied.
+pub(crate) struct TableFiles {
+    pub(crate) name: String,
+    pub(crate) tbl_path: PathBuf,
+    tbl: File,
+    bucket: File,
+    /// Where the header ends and the index begins
+    offset_header: u32,
+    /// The writes not yet applied to the files, in order
+    pending: Vec<PageWrite>,
+}
+
+impl TableFiles {
+    /// Opens the `.tbl` and `_bucket.bin` files of a table in the directory `db_dir`.
+    pub(crate) fn open(db_dir: &Path, table_name: &str) -> Result<Self, Error> {
+        let tbl_path = db_dir.join(format!("{}.tbl", table_name));
+
+        if !tbl_path.exists() {
+            return Err(Error::NotFound(format!("Table {}", table_name)));
+        }
+
+        TableFiles::open_paths(table_name, tbl_path, &db_dir.join(format!("{}_bucket.bin", table_name)))
+    }
+
+    /// Opens the files of a table stored at the given paths.
+    pub(crate) fn open_paths(table_name: &str, tbl_path: PathBuf, bucket_path: &Path) -> Result<Self, Error> {
+        let mut tbl = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&tbl_path)?;
+
+        let bucket = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(bucket_path)?;
+
+        let header = read_at(&mut tbl, 64, 4)?;
+        let offset_header = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+
+        if (offset_header as usize) < TBL_FIXED_HEADER_SIZE || offset_header as u64 > tbl.metadata()?.len() {
+            return Err(Error::Corrupt { table: table_name.to_string(), offset: 64 });
+        }
+
+        Ok(TableFiles {
+            name: table_name.to_string(),
+            tbl_path,
+            tbl,
+            bucket,
+            offset_header,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Reads the schema written in the header of the `.tbl` file.
+    pub(crate) fn read_schema(&mut self) -> Result<TableSchema, Error> {
+        let header = self.read(TableFile::Tbl, 0, self.offset_header as usize)
+            .map_err(|_| Error::Corrupt { table: self.name.clone(), offset: 64 })?;
+
+        parse_table_header(&self.name, &header)
+    }
+
+    /// Replaces the header of the `.tbl` file with the one of `schema`.
+    ///
+    /// The new header is written in place if it fits before the index, otherwise the
+    /// index and the data section are copied after it into a new `.tbl` file, which
+    /// replaces the old one. The bucket doesn't change in both cases, since its
+    /// addresses are relative to the data section.
+    ///
+    /// It's written directly, so there must be no pending writes.
+    pub(crate) fn write_header(&mut self, schema: &TableSchema) -> Result<(), Error> {
+        debug_assert!(self.pending.is_empty(), "header of {} written with pending writes", self.name);
+
+        let header = table_header_bytes(schema, self.offset_header as usize)?;
+
+        if header.len() == self.offset_header as usize {
+            return write_at(&mut self.tbl, 0, &header);
+        }
+
+        let grown_path = self.tbl_path.with_extension("tbl.grow");
+
+        if let Err(e) = self.copy_with_header(&grown_path, &header) {
+            let _ = std::fs::remove_file(&grown_path);
+            return Err(e);
+        }
+
+        std::fs::rename(&grown_path, &self.tbl_path)?;
+
+        self.tbl = OpenOptions::new().read(true).write(true).open(&self.tbl_path)?;
+        self.offset_header = header.len() as u32;
+
+        Ok(())
+    }
+
+    /// Writes `header` to a new file at `path`, followed by the index and the data section.
+    fn copy_with_header(&mut self, path: &Path, header: &[u8]) -> Result<(), Error> {
+        let mut copy = File::create(path)?;
+        copy.write_all(header)?;
+
+        self.tbl.seek(SeekFrom::Start(self.offset_header as u64))?;
+        io::copy(&mut self.tbl, &mut copy)?;
+
+        copy.sync_all()?;
+        Ok(())
+    }
+
+    /// Absolute position of the data section in the `.tbl` file.
+    pub(crate) fn data_start(&self) -> u64 {
+        self.offset_header as u64 + INDEX_SIZE as u64
+    }
+
+    /// Appends a record to the data section and indexes it.
+    ///
+    /// The next OID is read from the `last_OID` field of the `.tbl` header, then:
+    /// 1. the record is appended at the end of the data section, preceded by its
+    ///    header (see `RECORD_HEADER_SIZE`);
+    /// 2. a node with the OID and the address of the record is appended to
+    ///    `<table>_bucket.bin`. Its next node is the one the index slot pointed to,
+    ///    so colliding OIDs are chained starting from the most recent one;
+    /// 3. the index slot `fx_hash16(OID)` is pointed to the new node;
+    /// 4. `last_OID` is updated.
+    pub(crate) fn insert(&mut self, record: &[u8]) -> Result<OID, Error> {
+        let last_oid = read_u24(&self.read(TableFile::Tbl, 68, 3)?);
+
+        if last_oid >= MAX_OID {
+            return Err(Error::TableFull(self.name.clone()));
+        }
+        let oid = last_oid + 1;
+
+        self.insert_as(oid, record)?;
+
+        //HEADER
+        self.set_last_oid(oid)?;
+
+        Ok(OID { val: oid as i32 })
+    }
+
+    /// Steps 1-3 of `insert()`: stores and indexes a record with the given OID,
+    /// without touching `last_OID`. The OID must not be in the table already.
+    pub(crate) fn insert_as(&mut self, oid: u32, record: &[u8]) -> Result<(), Error> {
+        //DATA
+        let address = self.append_record(oid, record)?;
+
+        //BUCKET
+        let slot = self.slot(oid);
+        let head = self.read(TableFile::Tbl, slot, 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))?;
+
+        let bucket_end = self.len(TableFile::Bucket)?;
+        let node_num = (bucket_end / BUCKET_NODE_SIZE as u64) as u32 + 1;
+
+        let mut node: Vec<u8> = Vec::with_capacity(BUCKET_NODE_SIZE);
+        node.extend_from_slice(&u24_to_le_bytes(oid));
+        node.extend_from_slice(&address.to_le_bytes());
+        node.extend_from_slice(&u24_to_le_bytes(head));
+
+        self.write(TableFile::Bucket, bucket_end, &node);
+
+        //INDEX
+        self.write(TableFile::Tbl, slot, &node_num.to_le_bytes());
+
+        Ok(())
+    }
+
+    /// Writes the `last_OID` field of the `.tbl` header.
+    pub(crate) fn set_last_oid(&mut self, oid: u32) -> Result<(), Error> {
+        self.write(TableFile::Tbl, 68, &u24_to_le_bytes(oid));
+
+        Ok(())
+    }
+
+    /// Removes the pending writes from the table, to log them.
+    pub(crate) fn take_pending(&mut self) -> Vec<PageWrite> {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Applies writes to the files, without logging them.
+    ///
+    /// The files are not flushed, see `sync()`.
+    pub(crate) fn apply(&mut self, writes: &[PageWrite]) -> Result<(), Error> {
+        for write in writes {
+            write_at(self.file(write.file), write.offset, &write.bytes)?;
+        }
+
         Ok(())
-    } else {
-        Err(format!("Database {} does not exist", db_name))
     }
+
+    /// Applies the pending writes to the files, without logging them.
+    ///
+    /// Used for the files of a table that is not part of a database yet, like the
+    /// copy written by a migration.
+    pub(crate) fn apply_pending(&mut self) -> Result<(), Error> {
+        let pending = self.take_pending();
+        self.apply(&pending)
+    }
+
+    /// Drops the pending writes, leaving the files as they were.
+    pub(crate) fn discard_pending(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Flushes the `.tbl` and `_bucket.bin` files to the disk.
+    pub(crate) fn sync(&mut self) -> Result<(), Error> {
+        self.tbl.sync_all()?;
+        self.bucket.sync_all()?;
+
+        Ok(())
+    }
+
+    /// Reads a record through the index, given its OID.
+    ///
+    /// The OID is reduced to its index slot with `fx_hash16()`, then the chain of
+    /// nodes starting from the slot is walked in the `_bucket.bin` file until the
+    /// node with the same OID is found. The node contains the address of the record
+    /// in the data section.
+    ///
+    /// The record is returned with its absolute position in the `.tbl` file.
+    pub(crate) fn get(&mut self, oid: OID) -> Result<Option<(u64, Vec<u8>)>, Error> {
+        let Some(node) = self.find_node(oid)? else {
+            return Ok(None);
+        };
+
+        let record_start = self.data_start() + node.address as u64;
+        let header = self.read(TableFile::Tbl, record_start, RECORD_HEADER_SIZE)?;
+
+        if header[0] != RECORD_LIVE {
+            return Ok(None);
+        }
+        if read_u24(&header[1..4]) != oid.val as u32 {
+            return Err(Error::Corrupt { table: self.name.clone(), offset: record_start });
+        }
+
+        let length = u32::from_le_bytes([header[8], header[9], header[10], header[11]]) as usize;
+
+        self.read(TableFile::Tbl, record_start + RECORD_HEADER_SIZE as u64, length)
+            .map(|record| Some((record_start, record)))
+    }
+
+    /// Replaces a record with a new version of it, keeping its OID.
+    ///
+    /// If the new record fits in the capacity of the old one it's rewritten in place,
+    /// updating its length. Otherwise a copy is appended to the data section (as in
+    /// `insert()`), the node of the OID is pointed to it and the old record
+    /// is marked as `RECORD_DELETED`.
+    pub(crate) fn update(&mut self, oid: OID, record: &[u8]) -> Result<bool, Error> {
+        let Some(node) = self.find_node(oid)? else {
+            return Ok(false);
+        };
+
+        let record_start = self.data_start() + node.address as u64;
+        let header = self.read(TableFile::Tbl, record_start, RECORD_HEADER_SIZE)?;
+
+        if header[0] != RECORD_LIVE {
+            return Ok(false);
+        }
+
+        let capacity = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+
+        //IN PLACE
+        if record.len() <= capacity {
+            let mut buffer: Vec<u8> = Vec::with_capacity(4 + record.len());
+            buffer.extend_from_slice(&(record.len() as u32).to_le_bytes()); // Length
+            buffer.extend_from_slice(record);
+
+            self.write(TableFile::Tbl, record_start + 8, &buffer);
+            return Ok(true);
+        }
+
+        //RELOCATION
+        let address = self.append_record(oid.val as u32, record)?;
+
+        self.write(TableFile::Bucket, (node.num as u64 - 1) * BUCKET_NODE_SIZE as u64 + 3, &address.to_le_bytes());
+        self.write(TableFile::Tbl, record_start, &[RECORD_DELETED]);
+
+        Ok(true)
+    }
+
+    /// Deletes a record, given its OID.
+    ///
+    /// The record is not removed from the data section: its status byte is set to
+    /// `RECORD_DELETED`, so it's skipped by `RecordIter`. Then its node is unlinked
+    /// from the chain of its index slot, so it can no longer be found by `get()`:
+    /// - if it's the first node, the slot is pointed to the next one (or cleared);
+    /// - otherwise the node before it is pointed to the next one.
+    pub(crate) fn delete(&mut self, oid: OID) -> Result<bool, Error> {
+        let Some(node) = self.find_node(oid)? else {
+            return Ok(false);
+        };
+
+        //DATA
+        let record_start = self.data_start() + node.address as u64;
+        self.write(TableFile::Tbl, record_start, &[RECORD_DELETED]);
+
+        //BUCKET / INDEX
+        if node.prev == 0 {
+            let slot = self.slot(oid.val as u32);
+            self.write(TableFile::Tbl, slot, &node.next.to_le_bytes());
+        } else {
+            let prev_next = (node.prev as u64 - 1) * BUCKET_NODE_SIZE as u64 + 7;
+            self.write(TableFile::Bucket, prev_next, &u24_to_le_bytes(node.next));
+        }
+
+        Ok(true)
+    }
+
+    /// Position in the `.tbl` file of the index slot of an OID.
+    fn slot(&self, oid: u32) -> u64 {
+        self.offset_header as u64 + fx_hash16(oid) as u64 * 4
+    }
+
+    /// Appends a live record at the end of the data section, returning its address.
+    fn append_record(&mut self, oid: u32, record: &[u8]) -> Result<u32, Error> {
+        let end = self.len(TableFile::Tbl)?;
+        let address = u32::try_from(end - self.data_start())
+            .map_err(|_| Error::TableFull(self.name.clone()))?;
+
+        let mut buffer: Vec<u8> = Vec::with_capacity(RECORD_HEADER_SIZE + record.len());
+        buffer.push(RECORD_LIVE);
+        buffer.extend_from_slice(&u24_to_le_bytes(oid));
+        buffer.extend_from_slice(&(record.len() as u32).to_le_bytes()); // Capacity
+        buffer.extend_from_slice(&(record.len() as u32).to_le_bytes()); // Length
+        buffer.extend_from_slice(record);
+
+        self.write(TableFile::Tbl, end, &buffer);
+
+        Ok(address)
+    }
+
+    /// Walks the chain of the index slot of `oid` looking for its node.
+    fn find_node(&mut self, oid: OID) -> Result<Option<BucketNode>, Error> {
+        let oid = oid.val as u32;
+        let slot = self.slot(oid);
+        let head = self.read(TableFile::Tbl, slot, 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))?;
+
+        let nodes_num = self.len(TableFile::Bucket)? / BUCKET_NODE_SIZE as u64;
+
+        let mut prev = 0;
+        let mut num = head;
+        let mut steps = 0;
+
+        while num != 0 {
+            //A chain can't be longer than the bucket, otherwise it is a loop
+            steps += 1;
+            if num as u64 > nodes_num || steps > nodes_num {
+                return Err(Error::Corrupt { table: self.name.clone(), offset: (num as u64).saturating_sub(1) * BUCKET_NODE_SIZE as u64 });
+            }
+
+            let node = self.read(TableFile::Bucket, (num as u64 - 1) * BUCKET_NODE_SIZE as u64, BUCKET_NODE_SIZE)?;
+            let next = read_u24(&node[7..10]);
+
+            if read_u24(&node[0..3]) == oid {
+                return Ok(Some(BucketNode {
+                    num,
+                    prev,
+                    address: u32::from_le_bytes([node[3], node[4], node[5], node[6]]),
+                    next,
+                }));
+            }
+
+            prev = num;
+            num = next;
+        }
+
+        Ok(None)
+    }
+
+    /// Reads `len` bytes of a file at `offset`, with the pending writes applied to them.
+    fn read(&mut self, file: TableFile, offset: u64, len: usize) -> Result<Vec<u8>, Error> {
+        let end = offset + len as u64;
+
+        if end > self.len(file)? {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+
+        let on_disk = self.file(file).metadata()?.len();
+        let mut buffer = vec![0u8; len];
+
+        if offset < on_disk {
+            let available = (on_disk.min(end) - offset) as usize;
+            buffer[..available].copy_from_slice(&read_at(self.file(file), offset, available)?);
+        }
+
+        for write in self.pending.iter().filter(|write| write.file == file) {
+            let write_end = write.offset + write.bytes.len() as u64;
+            let (start, stop) = (write.offset.max(offset), write_end.min(end));
+
+            if start < stop {
+                buffer[(start - offset) as usize..(stop - offset) as usize]
+                    .copy_from_slice(&write.bytes[(start - write.offset) as usize..(stop - write.offset) as usize]);
+            }
+        }
+
+        Ok(buffer)
+    }
+
+    /// Keeps a write to a file, until it's logged.
+    fn write(&mut self, file: TableFile, offset: u64, bytes: &[u8]) {
+        self.pending.push(PageWrite { file, offset, bytes: bytes.to_vec() });
+    }
+
+    /// The length of a file, with the pending writes past its end.
+    fn len(&mut self, file: TableFile) -> Result<u64, Error> {
+        let on_disk = self.file(file).metadata()?.len();
+
+        Ok(self.pending.iter()
+            .filter(|write| write.file == file)
+            .map(|write| write.offset + write.bytes.len() as u64)
+            .fold(on_disk, u64::max))
+    }
+
+    fn file(&mut self, file: TableFile) -> &mut File {
+        match file {
+            TableFile::Tbl => &mut self.tbl,
+            TableFile::Bucket => &mut self.bucket,
+        }
+    }
+}
+
+/// A node of the `_bucket.bin` file found by `TableFiles::find_node()`.
+struct BucketNode {
+    /// Number of the node, starting from 1
+    num: u32,
+    /// Number of the node before it in the chain, 0 if it's the first one
+    prev: u32,
+    /// Address of the record, relative to the data section
+    address: u32,
+    /// Number of the next node in the chain, 0 if it's the last one
+    next: u32,
 }
 
+pub(crate) fn read_at(file: &mut File, offset: u64, len: usize) -> Result<Vec<u8>, Error> {
+    let mut buffer = vec![0u8; len];
 
-///Allows you to reinitialize a table.
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buffer)?;
+
+    Ok(buffer)
+}
+
+#[cfg(test)]
+thread_local! {
+    /// The number of calls to `write_at()` that succeed before one fails, to simulate
+    /// an I/O error in the tests. `None` if none fails.
+    pub(crate) static WRITES_BEFORE_FAILURE: std::cell::Cell<Option<usize>> = const { std::cell::Cell::new(None) };
+}
+
+pub(crate) fn write_at(file: &mut File, offset: u64, bytes: &[u8]) -> Result<(), Error> {
+    #[cfg(test)]
+    WRITES_BEFORE_FAILURE.with(|writes| match writes.get() {
+        Some(0) => {
+            writes.set(None);
+            Err(io::Error::other("simulated write failure"))
+        },
+        Some(left) => {
+            writes.set(Some(left - 1));
+            Ok(())
+        },
+        None => Ok(()),
+    })?;
+
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(bytes)?;
+
+    Ok(())
+}
+
+/// Iterator over the records of a table, returned by `Database::get_records()`.
 ///
-///It must be called **manually** by the developer 
-///when applying some change to the structure of one 
-///of the structs. The method clears the table and reinitializes it.
+/// It walks the data section of the `.tbl` file, starting from `offset_header + INDEX_SIZE`,
+/// and decodes one record at a time while it's iterated, so the table is never loaded
+/// into memory as a whole. Deleted records are skipped.
 ///
-///**Caution**: the method deletes all data within the table.
-pub fn reinitialize_table(_table_name: String, _db_name: String, _ref: Vec<String>, _fields: Vec<Field>)-> Result<(), String>{
-    todo!()
+/// Each item is the decoded record, with its OID field set, or the error that
+/// stopped the scan: after an error the iterator is exhausted.
+pub struct RecordIter<T: Objekt> {
+    scan: RawScan,
+    /// The database the objects are bound to (see `Objekt::bind()`)
+    db: Database,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Objekt> RecordIter<T> {
+    /// Opens a new handle to the `.tbl` file of a table, positioned on its first record.
+    pub(crate) fn open(table: &TableFiles, db: Database) -> Result<Self, Error> {
+        Ok(RecordIter {
+            scan: RawScan::open(table)?,
+            db,
+            _marker: PhantomData,
+        })
+    }
 }
 
+/// Sequential scan of the live records of a table, without decoding them.
+///
+/// It reads the `.tbl` file through its own handle, so the table can be
+/// modified through its `TableFiles` while the scan is open.
+pub(crate) struct RawScan {
+    reader: BufReader<File>,
+    /// Absolute position in the `.tbl` file of the next record header
+    position: u64,
+    /// The length of the `.tbl` file when it was last read
+    file_len: u64,
+    table_name: String,
+}
+
+impl RawScan {
+    /// Opens a new handle to the `.tbl` file of a table, positioned on its first record.
+    pub(crate) fn open(table: &TableFiles) -> Result<Self, Error> {
+        let mut tbl = File::open(&table.tbl_path)?;
+
+        let position = table.data_start();
+        tbl.seek(SeekFrom::Start(position))?;
+        let file_len = tbl.metadata()?.len();
+
+        Ok(RawScan {
+            reader: BufReader::new(tbl),
+            position,
+            file_len,
+            table_name: table.name.clone(),
+        })
+    }
+
+    /// Reads the next live record, without decoding it.
+    ///
+    /// It's returned with its OID and its absolute position in the `.tbl` file.
+    pub(crate) fn next_raw(&mut self) -> Result<Option<(OID, u64, Vec<u8>)>, Error> {
+        loop {
+            let mut header = [0u8; RECORD_HEADER_SIZE];
+            let record_start = self.position;
+            let corrupt = || Error::Corrupt { table: self.table_name.clone(), offset: record_start };
+
+            if self.reader.read(&mut header[..1])? == 0 {
+                return Ok(None);
+            }
+            self.reader.read_exact(&mut header[1..])
+                .map_err(|_| corrupt())?;
+
+            let oid = OID { val: read_u24(&header[1..4]) as i32 };
+            let capacity = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as u64;
+            let length = u32::from_le_bytes([header[8], header[9], header[10], header[11]]) as u64;
+
+            // The capacity is checked against the file before the record is allocated. The
+            // length is read again past the known one: records can be appended during the scan
+            let record_end = record_start + RECORD_HEADER_SIZE as u64 + capacity;
+            if record_end > self.file_len {
+                self.file_len = self.reader.get_ref().metadata()?.len();
+            }
 
-///[32 bytes of lenght; value; 32 bytes of lenght; value...]
-pub fn get_records(struct_name: String)-> Vec<u8>
-{
-    todo!()
+            if length > capacity || record_end > self.file_len {
+                return Err(corrupt());
+            }
+
+            self.position = record_end;
+
+            if header[0] != RECORD_LIVE {
+                self.reader.seek_relative(capacity as i64)?;
+                continue;
+            }
+
+            let mut record = vec![0u8; length as usize];
+            self.reader.read_exact(&mut record)
+                .map_err(|_| corrupt())?;
+            self.reader.seek_relative((capacity - length) as i64)?;
+
+            return Ok(Some((oid, record_start, record)));
+        }
+    }
+
+    /// Moves the scan to the end of the file, so that it returns no more records.
+    pub(crate) fn stop(&mut self) {
+        self.position = u64::MAX;
+        let _ = self.reader.seek(SeekFrom::End(0));
+    }
+}
+
+/// Decodes the record whose header is at `record_start`, sets its OID field and binds
+/// its references to `db`.
+///
+/// The offset of an `Error::CorruptField` becomes the position of the field in the `.tbl` file.
+pub(crate) fn decode_record<T: Objekt>(db: &Database, oid: OID, record_start: u64, record: Vec<u8>) -> Result<T, Error> {
+    match T::record_from_bytes(record) {
+        Ok(mut obj) => {
+            obj.set_oid(oid);
+            obj.bind(db);
+            Ok(obj)
+        },
+        Err(Error::CorruptField { table, field, offset, error }) => Err(Error::CorruptField {
+            table,
+            field,
+            offset: record_start + RECORD_HEADER_SIZE as u64 + offset,
+            error,
+        }),
+        Err(e) => Err(e),
+    }
+}
+
+impl<T: Objekt> Iterator for RecordIter<T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = match self.scan.next_raw() {
+            Ok(None) => return None,
+            Ok(Some((oid, record_start, record))) => {
+                decode_record(&self.db, oid, record_start, record)
+            },
+            Err(e) => Err(e),
+        };
+
+        // Stop at the first error, the position of the next record is unknown
+        if result.is_err() {
+            self.scan.stop();
+        }
+
+        Some(result)
+    }
 }
@@ -0,0 +1,247 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use super::file_manager::write_at;
+use crate::error::Error;
+use crate::support_mods::support_functions::fx_hash64;
+
+/// Kind byte of a log entry with a write to a file of a table.
+pub const LOG_WRITE: u8 = 1;
+
+/// Kind byte of the log entry that closes a transaction.
+pub const LOG_COMMIT: u8 = 2;
+
+/// The file of a table a `PageWrite` goes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TableFile {
+    /// The `.tbl` file: header, index and data section
+    Tbl = 0,
+    /// The `_bucket.bin` file
+    Bucket = 1,
+}
+
+/// A write to a file of a table, kept by `TableFiles` until its transaction is
+/// committed and logged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PageWrite {
+    pub(crate) file: TableFile,
+    /// Absolute position of the write in the file
+    pub(crate) offset: u64,
+    pub(crate) bytes: Vec<u8>,
+}
+
+/// The write-ahead log of a database, the `<name>.wal` file in its directory.
+///
+/// The writes of an operation on the records (the record appended to the data
+/// section, the index slot, the bucket node, `last_OID`...) are appended to the
+/// log as a transaction before any of them reaches the files of the tables:
+/// - an entry for each write: `LOG_WRITE` (1 byte), the length of the table name
+///   (1 byte), the table name (variable), the file (1 byte, see `TableFile`), the
+///   offset (8 bytes), the length of the bytes (4 bytes) and the bytes (variable);
+/// - a commit entry: `LOG_COMMIT` (1 byte), the number of writes (4 bytes) and the
+///   `fx_hash64()` of the write entries (8 bytes).
+///
+/// Once the log is flushed the writes are applied to the tables, which are flushed
+/// too, and the log is emptied. When a database is opened, `recover()` applies again
+/// the committed transactions left in the log by a crash and discards the incomplete
+/// one at its end, so the tables are never left with half of an operation.
+pub(crate) struct LogManager {
+    file: File,
+}
+
+impl LogManager {
+    /// Opens the log of the database `db_name` in `dir`, creating it if it doesn't exist.
+    pub(crate) fn open(dir: &Path, db_name: &str) -> Result<Self, Error> {
+        let path = dir.join(format!("{}.wal", db_name));
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        Ok(LogManager { file })
+    }
+
+    /// Appends a transaction with the writes of each table to the log and flushes it.
+    ///
+    /// After this the writes are durable: if they are not all applied to the tables,
+    /// `recover()` will apply them.
+    pub(crate) fn append(&mut self, tables: &[(String, Vec<PageWrite>)]) -> Result<(), Error> {
+        let mut entries: Vec<u8> = Vec::new();
+        let mut count: u32 = 0;
+
+        for (table_name, write) in tables.iter().flat_map(|(name, writes)| writes.iter().map(move |write| (name, write))) {
+            count += 1;
+
+            entries.push(LOG_WRITE);
+            entries.push(table_name.len() as u8);
+            entries.extend_from_slice(table_name.as_bytes());
+            entries.push(write.file as u8);
+            entries.extend_from_slice(&write.offset.to_le_bytes());
+            entries.extend_from_slice(&(write.bytes.len() as u32).to_le_bytes());
+            entries.extend_from_slice(&write.bytes);
+        }
+
+        entries.push(LOG_COMMIT);
+        entries.extend_from_slice(&count.to_le_bytes());
+        entries.extend_from_slice(&fx_hash64(&entries[..entries.len() - 5]).to_le_bytes());
+
+        self.file.seek(SeekFrom::End(0))?;
+        self.file.write_all(&entries)?;
+        self.file.sync_data()?;
+
+        Ok(())
+    }
+
+    /// The length of the log, where the next transaction will be appended.
+    pub(crate) fn len(&mut self) -> Result<u64, Error> {
+        Ok(self.file.metadata()?.len())
+    }
+
+    /// Cuts the log back to `len` bytes, dropping what was appended after.
+    pub(crate) fn truncate(&mut self, len: u64) -> Result<(), Error> {
+        self.file.set_len(len)?;
+        self.file.sync_data()?;
+
+        Ok(())
+    }
+
+    /// Empties the log, once its transactions have been applied and flushed.
+    pub(crate) fn checkpoint(&mut self) -> Result<(), Error> {
+        self.truncate(0)
+    }
+
+    /// Applies the committed transactions of the log to the tables in `dir`, then
+    /// empties it.
+    ///
+    /// The log is read up to the first entry that is incomplete, malformed or not
+    /// followed by its commit: that transaction was being written when the database
+    /// crashed, so none of its writes reached the tables and it's discarded.
+    /// Applying a transaction twice gives the same files, so it doesn't matter which
+    /// of its writes were applied before the crash.
+    ///
+    /// Returns the number of transactions applied.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NotFound` if a committed transaction writes to a table whose
+    /// files don't exist: the log is kept in that case.
+    pub(crate) fn recover(&mut self, dir: &Path) -> Result<usize, Error> {
+        let mut log = Vec::new();
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.read_to_end(&mut log)?;
+
+        if log.is_empty() {
+            return Ok(0);
+        }
+
+        let transactions = parse_log(&log);
+
+        let mut touched: Vec<File> = Vec::new();
+
+        for writes in &transactions {
+            for (table_name, write) in writes {
+                let path = match write.file {
+                    TableFile::Tbl => dir.join(format!("{}.tbl", table_name)),
+                    TableFile::Bucket => dir.join(format!("{}_bucket.bin", table_name)),
+                };
+
+                if !path.exists() {
+                    return Err(Error::NotFound(format!("Table {}", table_name)));
+                }
+
+                let mut file = OpenOptions::new().write(true).open(&path)?;
+                write_at(&mut file, write.offset, &write.bytes)?;
+                touched.push(file);
+            }
+        }
+
+        for file in touched {
+            file.sync_all()?;
+        }
+
+        self.checkpoint()?;
+
+        Ok(transactions.len())
+    }
+}
+
+/// The committed transactions of a log, in order.
+///
+/// The parsing stops at the first entry that can't be read or at a commit that
+/// doesn't match the entries before it, dropping the transaction it belongs to.
+fn parse_log(log: &[u8]) -> Vec<Vec<(String, PageWrite)>> {
+    let mut transactions = Vec::new();
+    let mut reader = LogReader { log, position: 0 };
+
+    let mut writes = Vec::new();
+    let mut transaction_start = 0;
+
+    while let Some(kind) = reader.take(1).map(|bytes| bytes[0]) {
+        match kind {
+            LOG_WRITE => match reader.write() {
+                Some(write) => writes.push(write),
+                None => break,
+            },
+            LOG_COMMIT => {
+                let entries_end = reader.position - 1;
+
+                let Some(count) = reader.u32() else { break };
+                let Some(hash) = reader.u64() else { break };
+
+                if count as usize != writes.len() || hash != fx_hash64(&log[transaction_start..entries_end]) {
+                    break;
+                }
+
+                transactions.push(std::mem::take(&mut writes));
+                transaction_start = reader.position;
+            },
+            _ => break,
+        }
+    }
+
+    transactions
+}
+
+/// Cursor over the bytes of a log, returning `None` past its end.
+struct LogReader<'a> {
+    log: &'a [u8],
+    position: usize,
+}
+
+impl<'a> LogReader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.log.get(self.position..self.position.checked_add(len)?)?;
+        self.position += len;
+
+        Some(bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take(8).map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    /// The rest of a `LOG_WRITE` entry, after its kind byte.
+    fn write(&mut self) -> Option<(String, PageWrite)> {
+        let name_len = self.take(1)?[0] as usize;
+        let table_name = String::from_utf8(self.take(name_len)?.to_vec()).ok()?;
+
+        let file = match self.take(1)?[0] {
+            0 => TableFile::Tbl,
+            1 => TableFile::Bucket,
+            _ => return None,
+        };
+        let offset = self.u64()?;
+        let len = self.u32()? as usize;
+        let bytes = self.take(len)?.to_vec();
+
+        Some((table_name, PageWrite { file, offset, bytes }))
+    }
+}
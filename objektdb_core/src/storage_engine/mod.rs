@@ -4,6 +4,16 @@
 /// organizing data on disk. 
 pub mod file_manager;
 
+/// The `database` module provides the `Database` handle, through which
+/// tables and records of a database are accessed.
+pub mod database;
+
+/// The `raw_record` module provides `RawRecord`, the field-level view of a record
+/// used to migrate tables to a new schema.
+pub mod raw_record;
+
+/// The `log_manager` module provides the write-ahead log, through which the changes
+/// to the records reach the files of the tables.
 pub(crate) mod log_manager;
 
 pub(crate) mod buffer_manager;
\ No newline at end of file
@@ -0,0 +1,120 @@
+use std::ops::Range;
+
+use crate::error::Error;
+use crate::support_mods::field::{Field, OID};
+use crate::traits::from_bytes::{DecodeError, FromBytes};
+
+/// A record read with the schema stored in its `.tbl` file, instead of the one of its struct.
+///
+/// It gives access to the values of the record field by field, so that a record
+/// written with an old version of a struct can be converted to the new one
+/// (see `Database::migrate()`).
+///
+/// # Example
+/// ```ignore
+/// db.migrate(|old: RawRecord| {
+///     Ok(Person {
+///         id: old.oid(),
+///         name: Primitive { val: old.get::<String>("name")? },
+///         // age was a u8
+///         age: Primitive { val: old.get::<u8>("age")? as u16 },
+///     })
+/// })?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct RawRecord {
+    table: String,
+    oid: OID,
+    /// Absolute position of the payload in the `.tbl` file
+    payload_start: u64,
+    data: Vec<u8>,
+    /// The fields present in the record, with the position of their value in `data`
+    values: Vec<(Field, Range<usize>)>,
+}
+
+impl RawRecord {
+    /// Splits a record payload into the values of the fields of `fields`, in order.
+    ///
+    /// A record can end before its last fields, which are then missing.
+    pub(crate) fn parse(table: &str, fields: &[Field], oid: OID, payload_start: u64, data: Vec<u8>) -> Result<Self, Error> {
+        let mut values = Vec::with_capacity(fields.len());
+        let mut start = 0;
+
+        for field in fields {
+            if start >= data.len() {
+                break;
+            }
+
+            let end = start + 1 + data[start] as usize;
+            if end > data.len() {
+                return Err(Error::CorruptField {
+                    table: table.to_string(),
+                    field: field.name.clone(),
+                    offset: payload_start + start as u64,
+                    error: DecodeError::Truncated,
+                });
+            }
+
+            values.push((field.clone(), start + 1..end));
+            start = end;
+        }
+
+        Ok(RawRecord {
+            table: table.to_string(),
+            oid,
+            payload_start,
+            data,
+            values,
+        })
+    }
+
+    /// The OID of the record.
+    pub fn oid(&self) -> OID {
+        self.oid
+    }
+
+    /// The fields present in the record, as described by the stored schema.
+    pub fn fields(&self) -> impl Iterator<Item = &Field> {
+        self.values.iter().map(|(field, _)| field)
+    }
+
+    /// The encoded value of a field, `None` if the record doesn't have it.
+    pub fn bytes(&self, name: &str) -> Option<&[u8]> {
+        self.value(name).map(|(_, range)| &self.data[range.clone()])
+    }
+
+    /// Decodes the value of a field.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NotFound` if the record doesn't have the field, `Error::CorruptField`
+    /// if its value can't be decoded as a `V`.
+    pub fn get<V: FromBytes>(&self, name: &str) -> Result<V, Error> {
+        let (field, range) = self.value(name)
+            .ok_or_else(|| Error::NotFound(format!("Field {} of {}", name, self.table)))?;
+
+        V::from_bytes(&self.data[range.clone()]).map_err(|error| Error::CorruptField {
+            table: self.table.clone(),
+            field: field.name.clone(),
+            offset: self.payload_start + range.start as u64,
+            error,
+        })
+    }
+
+    /// The payload of the record with the value of a field replaced with `value`,
+    /// `None` if the record doesn't have the field.
+    pub(crate) fn with_value(&self, name: &str, value: &[u8]) -> Option<Vec<u8>> {
+        let (_, range) = self.value(name)?;
+
+        let mut data = self.data[..range.start - 1].to_vec();
+        data.push(value.len() as u8);
+        data.extend_from_slice(value);
+        data.extend_from_slice(&self.data[range.end..]);
+
+        Some(data)
+    }
+
+    fn value(&self, name: &str) -> Option<&(Field, Range<usize>)> {
+        self.values.iter().find(|(field, _)| field.name == name)
+    }
+}
@@ -1,21 +1,169 @@
+use super::support_functions::fx_hash64;
+
 ///The Field type represents, as the name suggests, 
 ///the struct fields to which the `objekt` macro is applied. 
 ///
 ///It is used by some functions within `file_manager`. 
 ///It grants some methods for handling fields, especially 
 ///with regard to reading from binary files.
+///
+///`type_` is the name of the type of the value: `OID` for the
+///field containing the object id, `T` for a `Primitive<T>` and
+///`Ref<T>` for a reference, which is also flagged `is_fk`.
+///`has_default` is set for the fields with `#[objekt(default)]`,
+///which can be missing at the end of older records.
+///`on_delete` is what happens to the record when the object a
+///foreign key refers to is deleted, see `OnDelete`.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Field{
     pub name: String,
     pub is_oid: bool,
-    
+    pub is_fk: bool,
+    pub has_default: bool,
+    pub on_delete: OnDelete,
+    pub type_: String,
+}
+
+impl Field{
+    ///The table a foreign key points at, read from its `Ref<T>` type.
+    pub fn ref_target(&self) -> Option<&str>{
+        if !self.is_fk {
+            return None;
+        }
+
+        self.type_.strip_prefix("Ref<")?.strip_suffix('>')
+    }
+}
+
+///What happens to a record whose foreign key refers to an object that is
+///being deleted, set with `#[objekt(on_delete = "...")]` on a `Ref` field.
+///
+///It's enforced by `Database::delete_record()`, which finds the referencing
+///tables through the catalog and their references section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnDelete{
+    ///The object can't be deleted while the record refers to it (`Error::RestrictedDelete`).
+    #[default]
+    Restrict,
+    ///The record is deleted with the object.
+    Cascade,
+    ///The reference in the record is replaced with the null reference (OID 0).
+    SetNull,
 }
 
+///The schema of a table, as written in the header of its `.tbl` file.
+///
+///It's returned by `Database::read_schema()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableSchema{
+    pub name: String,
+    pub last_oid: OID,
+    ///1 when the table is created, incremented every time its fields change.
+    pub version: u16,
+    pub references: Vec<String>,
+    pub fields: Vec<Field>,
+    pub methods: Vec<String>,
+}
 
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct OID{
     pub val: i32
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Primitive<T>{
     pub val: T
-}   
\ No newline at end of file
+}   
+///The differences between the schema stored in a `.tbl` file and the
+///fields of the struct of the table, returned in `Error::SchemaMismatch`.
+///
+///Fields are matched by name.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SchemaDiff{
+    ///Fields of the struct that are not in the stored schema.
+    pub added: Vec<Field>,
+    ///Fields of the stored schema that are not in the struct.
+    pub removed: Vec<Field>,
+    ///Fields whose type changed, as (stored field, struct field).
+    pub retyped: Vec<(Field, Field)>,
+    ///Whether the fields in both schemas are stored in a different order.
+    pub reordered: bool,
+}
+
+impl SchemaDiff{
+    ///Whether the records of the stored schema can be decoded by the struct.
+    pub fn is_empty(&self) -> bool{
+        self.added.is_empty() && self.removed.is_empty() && self.retyped.is_empty() && !self.reordered
+    }
+}
+
+impl TableSchema{
+    ///Compares the stored schema with the fields of a struct.
+    pub fn diff(&self, fields: &[Field]) -> SchemaDiff{
+        let find = |fields: &[Field], name: &str| fields.iter().position(|f| f.name == name);
+
+        let mut diff = SchemaDiff::default();
+
+        for field in fields{
+            match find(&self.fields, &field.name){
+                None => diff.added.push(field.clone()),
+                Some(i) if self.fields[i].type_ != field.type_ || self.fields[i].is_fk != field.is_fk => {
+                    diff.retyped.push((self.fields[i].clone(), field.clone()));
+                },
+                Some(_) => {},
+            }
+        }
+
+        diff.removed = self.fields.iter()
+            .filter(|f| find(fields, &f.name).is_none())
+            .cloned()
+            .collect();
+
+        //the fields in both schemas, in the order of each one
+        let stored: Vec<&str> = self.fields.iter()
+            .map(|f| f.name.as_str())
+            .filter(|name| find(fields, name).is_some())
+            .collect();
+        let current: Vec<&str> = fields.iter()
+            .map(|f| f.name.as_str())
+            .filter(|name| find(&self.fields, name).is_some())
+            .collect();
+
+        diff.reordered = stored != current;
+
+        diff
+    }
+
+    ///Whether `fields` are the stored fields followed by new fields with a default
+    ///value, so that the old records can still be decoded and the table doesn't
+    ///need to be migrated.
+    pub fn is_extended_by(&self, fields: &[Field]) -> bool{
+        let same = |stored: &Field, field: &Field| {
+            stored.name == field.name && stored.type_ == field.type_ && stored.is_fk == field.is_fk
+        };
+
+        fields.len() > self.fields.len()
+            && self.fields.iter().zip(fields).all(|(stored, field)| same(stored, field))
+            && fields[self.fields.len()..].iter().all(|field| field.has_default)
+    }
+
+    ///A hash of the fields, stored in the catalog of the `.db` file.
+    ///
+    ///It changes whenever the fields, their types or their flags change,
+    ///so it tells whether the header of a `.tbl` file is the one in the catalog.
+    pub fn schema_hash(&self) -> u64{
+        let mut bytes = Vec::new();
+
+        for field in &self.fields{
+            bytes.extend_from_slice(field.name.as_bytes());
+            bytes.push(0);
+            bytes.extend_from_slice(field.type_.as_bytes());
+            bytes.push(0);
+            bytes.push(field.is_oid as u8 | (field.is_fk as u8) << 1 | (field.has_default as u8) << 2);
+            bytes.push(field.on_delete as u8);
+        }
+
+        fx_hash64(&bytes)
+    }
+}
@@ -7,4 +7,8 @@ pub mod field;
 ///The `support_functions` module provides a set of functions useful for
 /// general operation and support for the project.
 pub mod support_functions;
-pub mod set;
\ No newline at end of file
+pub mod set;
+
+///The `reference` module provides `Ref` and `RefSet`, the field types of the
+/// relationships between an object and the objects of another table.
+pub mod reference;
@@ -0,0 +1,316 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::error::Error;
+use crate::storage_engine::database::Database;
+use crate::support_mods::field::{Field, OnDelete, OID};
+use crate::traits::objekt::Objekt;
+
+///A reference to an object of the table of `T`, stored as its OID.
+///
+///A `Ref<T>` field is a foreign key: the table of `T` is written in the references
+///section of the header of the table and the field is flagged `is_fk`. The object is
+///not read with the one that contains the reference, but when `load()` is called.
+///
+///The OID 0, which is never assigned, is the null reference (`Ref::default()`).
+///
+/// # Example
+/// ```ignore
+/// #[derive(Objekt)]
+/// struct Person {
+///     id: OID,
+///     name: Primitive<String>,
+///     employer: Ref<Company>,
+/// }
+///
+/// let acme = db.companies.push(company)?;
+/// let oid = db.people.push(Person { employer: Ref::new(acme), .. })?;
+///
+/// let employer = db.people.get(oid)?.unwrap().employer.load()?;
+/// ```
+pub struct Ref<T>{
+    pub oid: OID,
+    ///The database the containing object was read from
+    db: Option<Database>,
+    _marker: PhantomData<T>,
+}
+
+impl<T> Ref<T>{
+    ///A reference to the object with the given OID, not bound to a database.
+    pub fn new(oid: OID) -> Self{
+        Ref{
+            oid,
+            db: None,
+            _marker: PhantomData,
+        }
+    }
+
+    ///Whether it is the null reference.
+    pub fn is_null(&self) -> bool{
+        self.oid.val == 0
+    }
+
+    ///Binds the reference to the database containing the table of `T`.
+    ///
+    ///It's called by the storage engine on the objects it reads (see `Objekt::bind()`).
+    pub fn bind(&mut self, db: &Database){
+        self.db = Some(db.clone());
+    }
+}
+
+impl<T: Objekt> Ref<T>{
+    ///Reads the referenced object from the database the reference is bound to.
+    ///
+    ///Returns `Ok(None)` if the reference is null or the object doesn't exist anymore.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Unbound` if the reference was not read from a database: use
+    /// `load_from()` instead.
+    pub fn load(&self) -> Result<Option<T>, Error>{
+        match &self.db {
+            Some(db) => self.load_from(db),
+            None => Err(Error::Unbound(T::get_table_name())),
+        }
+    }
+
+    ///Reads the referenced object from `db`.
+    pub fn load_from(&self, db: &Database) -> Result<Option<T>, Error>{
+        if self.is_null() {
+            return Ok(None);
+        }
+
+        db.get_object::<T>(self.oid)
+    }
+}
+
+impl<T> Default for Ref<T>{
+    fn default() -> Self{
+        Ref::new(OID{ val: 0 })
+    }
+}
+
+impl<T> From<OID> for Ref<T>{
+    fn from(oid: OID) -> Self{
+        Ref::new(oid)
+    }
+}
+
+impl<T> Clone for Ref<T>{
+    fn clone(&self) -> Self{
+        Ref{
+            oid: self.oid,
+            db: self.db.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+///Two references are equal if they point at the same OID, bound or not.
+impl<T> PartialEq for Ref<T>{
+    fn eq(&self, other: &Self) -> bool{
+        self.oid == other.oid
+    }
+}
+
+impl<T> fmt::Debug for Ref<T>{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        f.debug_tuple("Ref").field(&self.oid.val).finish()
+    }
+}
+
+///The name of the join table of the `RefSet` field `field` of the struct of `owner_table`.
+///
+///It contains a dot, which can't be in the name of a struct, so it's never the table of one.
+pub fn join_table_name(owner_table: &str, field: &str) -> String{
+    format!("{}.{}", owner_table, field)
+}
+
+///A set of references to objects of the table of `T`, for one-to-many and many-to-many relations.
+///
+///The references are not stored in the record of the object that contains the set, but in a
+///join table created with the table of the object (see `join_table_name()`): each record of
+///the join table is a pair of references, `owner` to the object and `target` to an object of `T`.
+///
+///The set is bound to the object when it's read from the database, so it must be read
+///(or read again after `push()`) before objects can be added.
+///
+/// # Example
+/// ```ignore
+/// #[derive(Objekt)]
+/// struct User {
+///     id: OID,
+///     name: Primitive<String>,
+///     groups: RefSet<Group>,
+/// }
+///
+/// let user = db.users.get(oid)?.unwrap();
+/// user.groups.add(admins)?;
+///
+/// for group in user.groups.iter()? {
+///     println!("{}", group?.name.val);
+/// }
+/// ```
+pub struct RefSet<T>{
+    binding: Option<Binding>,
+    _marker: PhantomData<T>,
+}
+
+///The object a `RefSet` belongs to.
+#[derive(Clone)]
+struct Binding{
+    db: Database,
+    join_table: String,
+    owner: OID,
+}
+
+impl<T> RefSet<T>{
+    ///Binds the set to the object `owner` of `owner_table`, whose field is `field`.
+    ///
+    ///It's called by the storage engine on the objects it reads (see `Objekt::bind()`).
+    pub fn bind(&mut self, db: &Database, owner_table: &str, field: &str, owner: OID){
+        self.binding = Some(Binding{
+            db: db.clone(),
+            join_table: join_table_name(owner_table, field),
+            owner,
+        });
+    }
+}
+
+impl<T: Objekt> RefSet<T>{
+    ///The fields of the join table of a set whose owner is in `owner_table`.
+    ///
+    ///Deleting the owner or the target deletes the records that refer to it.
+    pub fn join_fields(owner_table: &str) -> Vec<Field>{
+        let reference = |name: &str, table: String| Field{
+            name: name.to_string(),
+            is_oid: false,
+            is_fk: true,
+            has_default: false,
+            on_delete: OnDelete::Cascade,
+            type_: format!("Ref<{}>", table),
+        };
+
+        vec![
+            Field{
+                name: "id".to_string(),
+                is_oid: true,
+                is_fk: false,
+                has_default: false,
+                on_delete: OnDelete::Restrict,
+                type_: "OID".to_string(),
+            },
+            reference("owner", owner_table.to_string()),
+            reference("target", T::get_table_name()),
+        ]
+    }
+
+    ///Creates the join table of the field `field` of the struct of `owner_table`, or checks
+    ///its schema. It's called by `Objekt::new()` of the struct.
+    pub fn ensure_join_table(db: &Database, owner_table: &str, field: &str) -> Result<(), Error>{
+        db.ensure_table(
+            join_table_name(owner_table, field),
+            vec![owner_table.to_string(), T::get_table_name()],
+            Self::join_fields(owner_table),
+            vec![]
+        )
+    }
+
+    ///The OIDs of the objects in the set, in the order in which they were added.
+    pub fn oids(&self) -> Result<Vec<OID>, Error>{
+        Ok(self.rows()?.into_iter().map(|(_, target)| target).collect())
+    }
+
+    ///Whether the object with the given OID is in the set.
+    pub fn contains(&self, target: OID) -> Result<bool, Error>{
+        Ok(self.rows()?.iter().any(|(_, oid)| *oid == target))
+    }
+
+    ///Adds the object with the given OID to the set.
+    ///
+    ///Returns `Ok(false)` if it was already in it, `Error::NotFound` if there is no object
+    ///with that OID.
+    pub fn add(&self, target: OID) -> Result<bool, Error>{
+        let binding = self.binding()?;
+
+        binding.db.add_join_row(&binding.join_table, binding.owner, &T::get_table_name(), target)
+    }
+
+    ///Removes the object with the given OID from the set. The object itself is not deleted.
+    ///
+    ///Returns `Ok(false)` if it was not in the set.
+    pub fn remove(&self, target: OID) -> Result<bool, Error>{
+        let binding = self.binding()?;
+
+        binding.db.remove_join_row(&binding.join_table, binding.owner, target)
+    }
+
+    ///Reads the objects of the set, one at a time while the iterator is consumed.
+    ///
+    ///The objects that were deleted after being added are skipped.
+    pub fn iter(&self) -> Result<impl Iterator<Item = Result<T, Error>> + '_, Error>{
+        let db = &self.binding()?.db;
+
+        Ok(self.oids()?.into_iter().filter_map(move |oid| db.get_object::<T>(oid).transpose()))
+    }
+
+    ///The number of references in the set.
+    pub fn len(&self) -> Result<usize, Error>{
+        Ok(self.rows()?.len())
+    }
+
+    ///Whether the set has no references.
+    pub fn is_empty(&self) -> Result<bool, Error>{
+        Ok(self.len()? == 0)
+    }
+
+    fn binding(&self) -> Result<&Binding, Error>{
+        self.binding.as_ref().ok_or_else(|| Error::Unbound(T::get_table_name()))
+    }
+
+    ///The records of the join table that belong to the owner: their OID and the one of the target.
+    fn rows(&self) -> Result<Vec<(OID, OID)>, Error>{
+        let binding = self.binding()?;
+
+        binding.db.join_rows(&binding.join_table, binding.owner)
+    }
+}
+
+impl<T> Default for RefSet<T>{
+    ///A set that is not bound to an object yet.
+    fn default() -> Self{
+        RefSet{
+            binding: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for RefSet<T>{
+    fn clone(&self) -> Self{
+        RefSet{
+            binding: self.binding.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+///The content of a set is in the database, so two sets are equal if they belong to the same object.
+impl<T> PartialEq for RefSet<T>{
+    fn eq(&self, other: &Self) -> bool{
+        match (&self.binding, &other.binding) {
+            (Some(a), Some(b)) => a.join_table == b.join_table && a.owner == b.owner,
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+impl<T> fmt::Debug for RefSet<T>{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        match &self.binding {
+            Some(binding) => write!(f, "RefSet({}, owner {})", binding.join_table, binding.owner.val),
+            None => write!(f, "RefSet(unbound)"),
+        }
+    }
+}
@@ -1,8 +1,91 @@
+use std::marker::PhantomData;
+
+use crate::error::Error;
+use crate::storage_engine::{database::Database, file_manager::RecordIter};
+use crate::support_mods::field::OID;
+use crate::traits::objekt::Objekt;
+
+///A collection of the objects of type `T`, bound to the table of `T` in a database.
+///
+///The fields of a struct with `#[odb]` are `Set`s: its `new()` binds each
+///of them to its table. The set holds no objects in memory, every method
+///goes through the storage engine, so a pushed object is persisted immediately.
+///
+/// # Example
+/// ```ignore
+/// let library = Library::new()?;
+///
+/// let oid = library.books.push(book)?;
+/// let dune = library.books.get(oid)?;
+/// let long = library.books.filter(|book| book.pages.val > 500)?;
+/// ```
 pub struct Set<T>{
-    pub collection: Vec<T>,
+    db: Database,
+    _marker: PhantomData<T>,
 }
-impl<T> Set<T>{
-    pub fn push(&self) -> Result<(), String>{
-        todo!()
+
+impl<T: Objekt> Set<T>{
+    ///Binds a set to the table of `T` in `db`.
+    ///
+    ///The table is created if it doesn't exist, otherwise its schema is checked
+    ///with `Objekt::new()`, whose errors are returned.
+    pub fn open(db: &Database) -> Result<Self, Error>{
+        T::new(db)?;
+
+        Ok(Set{
+            db: db.clone(),
+            _marker: PhantomData,
+        })
     }
- }
\ No newline at end of file
+
+    ///The database containing the table of the set.
+    pub fn db(&self) -> &Database{
+        &self.db
+    }
+
+    ///Appends an object to the table and returns the OID assigned to it.
+    pub fn push(&self, item: T) -> Result<OID, Error>{
+        self.db.insert_record(T::get_table_name(), item.to_bytes()?)
+    }
+
+    ///Reads the object with the given OID, through the index of the table.
+    ///
+    ///Returns `Ok(None)` if there is no object with that OID.
+    pub fn get(&self, oid: OID) -> Result<Option<T>, Error>{
+        self.db.get_object::<T>(oid)
+    }
+
+    ///Deletes the object with the given OID from the table.
+    ///
+    ///Returns `Ok(false)` if there is no object with that OID.
+    pub fn remove(&self, oid: OID) -> Result<bool, Error>{
+        self.db.delete_record(T::get_table_name(), oid)
+    }
+
+    ///Scans the table, reading the objects lazily while the iterator is consumed.
+    pub fn iter(&self) -> Result<RecordIter<T>, Error>{
+        self.db.get_records::<T>()
+    }
+
+    ///The number of objects in the table.
+    ///
+    ///The table is scanned to count them, without decoding the records.
+    pub fn len(&self) -> Result<usize, Error>{
+        self.db.count_records(T::get_table_name())
+    }
+
+    ///Whether the table has no objects.
+    pub fn is_empty(&self) -> Result<bool, Error>{
+        Ok(self.len()? == 0)
+    }
+
+    ///Reads the objects of the table for which `condition` returns `true`.
+    pub fn filter<F>(&self, condition: F) -> Result<Vec<T>, Error>
+    where
+        F: Fn(&T) -> bool
+    {
+        self.iter()?
+            .filter(|obj| obj.as_ref().map_or(true, &condition))
+            .collect()
+    }
+}
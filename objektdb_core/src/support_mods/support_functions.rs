@@ -1,6 +1,8 @@
-pub(crate) fn string_padding(vec: &mut Vec<u8>, str: String, tot_len: usize)-> Result<(), String>{
+use crate::error::Error;
+
+pub(crate) fn string_padding(vec: &mut Vec<u8>, str: String, tot_len: usize)-> Result<(), Error>{
     if tot_len < str.len(){
-        return Err(String::from("string too long"));
+        return Err(Error::NameTooLong(str));
     }
 
     vec.extend_from_slice(&vec![0u8; tot_len-str.len()]);
@@ -8,4 +10,44 @@ pub(crate) fn string_padding(vec: &mut Vec<u8>, str: String, tot_len: usize)-> R
     Ok(())
 }
 
+///Appends a string preceded by its length in 1 byte, as the names of the
+///fields, types and methods in the header of a `.tbl` file.
+pub(crate) fn push_short_str(vec: &mut Vec<u8>, str: &str)-> Result<(), Error>{
+    let len = u8::try_from(str.len())
+        .map_err(|_| Error::NameTooLong(str.to_string()))?;
+
+    vec.push(len);
+    vec.extend_from_slice(str.as_bytes());
+    Ok(())
+}
+
+
+/// Seed of FxHasher (the hash function used by rustc), for 64-bit words.
+const FX_SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+///Reduces an OID to the 16-bit hash used to address a slot of the index section.
+///
+///It is a single round of FxHasher on the OID, keeping the 16 most significant
+///bits, which are the best distributed ones.
+pub(crate) fn fx_hash16(oid: u32) -> u16 {
+    ((oid as u64).wrapping_mul(FX_SEED) >> 48) as u16
+}
+
+///Hashes a sequence of bytes with FxHasher, one byte per round.
+///
+///The result only depends on `bytes`, so it can be stored in the files.
+pub(crate) fn fx_hash64(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0u64, |hash, &byte| (hash.rotate_left(5) ^ byte as u64).wrapping_mul(FX_SEED))
+}
 
+///Reads a 3-byte little-endian unsigned integer (OIDs and bucket node numbers).
+pub(crate) fn read_u24(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0])
+}
+
+///Encodes a value as a 3-byte little-endian unsigned integer, the
+///opposite of `read_u24()`. The most significant byte is discarded.
+pub(crate) fn u24_to_le_bytes(val: u32) -> [u8; 3] {
+    let bytes = val.to_le_bytes();
+    [bytes[0], bytes[1], bytes[2]]
+}
@@ -0,0 +1,1169 @@
+use super::super::{error::Error, storage_engine::{database::{CatalogCheck, Database}, file_manager::*}, support_mods::{field::*, support_functions::fx_hash16}, traits::objekt::Objekt};
+use std::fs;
+use std::path::Path;
+
+//Database::create() / Database::open() tests
+#[test]
+fn test_database_at_arbitrary_path() {
+    let parent = "database_parent_dir";
+    let _ = fs::remove_dir_all(parent);
+    fs::create_dir(parent).unwrap();
+
+    let path = Path::new(parent).join("nested_db");
+    let db = Database::create(&path).unwrap();
+
+    assert_eq!(db.name(), "nested_db");
+    assert!(path.join("nested_db.db").exists());
+
+    db.create_table("users".to_string(), vec![], vec![], vec![]).unwrap();
+    assert!(path.join("users.tbl").exists());
+    assert!(path.join("users_bucket.bin").exists());
+
+    fs::remove_dir_all(parent).unwrap();
+}
+
+#[test]
+fn test_database_open_persists_tables() {
+    let db_name = "database_reopen_db";
+    let db = setup_table(db_name, "items");
+    db.create_table("others".to_string(), vec![], vec![], vec![]).unwrap();
+    let oid = db.insert_record("items".to_string(), vec![1, 5]).unwrap();
+    drop(db);
+
+    let db = Database::open(db_name).unwrap();
+    let header = db.header().unwrap();
+
+    assert_eq!(header.version, DB_VERSION);
+    assert_eq!(header.num_of_tables, 2);
+    assert_eq!(db.get_record("items".to_string(), oid).unwrap(), Some(vec![1, 5]));
+
+    // The count is in byte 5, the version in byte 4 is untouched
+    let content = fs::read(Path::new(db_name).join(format!("{}.db", db_name))).unwrap();
+    assert_eq!(content.len(), DB_HEADER_SIZE + 2 * (4 + 64 + 8 + 1) + "items.tbl".len() + "others.tbl".len());
+    assert_eq!(&content[4..6], &[DB_VERSION, 2]);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_two_databases_open() {
+    let first = setup_table("database_first_db", "items");
+    let second = setup_table("database_second_db", "items");
+
+    first.insert_record("items".to_string(), vec![1, 1]).unwrap();
+    second.insert_record("items".to_string(), vec![1, 2]).unwrap();
+
+    assert_eq!(first.get_record("items".to_string(), OID { val: 1 }).unwrap(), Some(vec![1, 1]));
+    assert_eq!(second.get_record("items".to_string(), OID { val: 1 }).unwrap(), Some(vec![1, 2]));
+
+    fs::remove_dir_all("database_first_db").unwrap();
+    fs::remove_dir_all("database_second_db").unwrap();
+}
+
+#[test]
+fn test_database_open_twice_shares_the_handle() {
+    let db_name = "database_open_twice_db";
+    let db = setup_table(db_name, "items");
+
+    let other = Database::open(std::path::PathBuf::from(".").join(db_name)).unwrap();
+    other.insert_record("items".to_string(), vec![1, 5]).unwrap();
+
+    // Replacing the files through one handle replaces the files used by the other
+    db.reinitialize_table("items".to_string(), vec![], vec![], vec![]).unwrap();
+    let oid = other.insert_record("items".to_string(), vec![1, 6]).unwrap();
+
+    assert_eq!(db.get_record("items".to_string(), oid).unwrap(), Some(vec![1, 6]));
+    drop(db);
+    drop(other);
+
+    let db = Database::open(db_name).unwrap();
+    assert_eq!(db.get_record("items".to_string(), oid).unwrap(), Some(vec![1, 6]));
+    assert_eq!(db.count_records("items".to_string()).unwrap(), 1);
+    drop(db);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_database_open_missing() {
+    let result = Database::open("database_missing_db");
+
+    assert!(matches!(result, Err(Error::NotFound(_))));
+}
+
+#[test]
+fn test_database_open_invalid_magic() {
+    let db_name = "database_magic_db";
+    let _ = fs::remove_dir_all(db_name);
+    fs::create_dir(db_name).unwrap();
+    fs::write(Path::new(db_name).join(format!("{}.db", db_name)), [0u8; DB_HEADER_SIZE]).unwrap();
+
+    let result = Database::open(db_name);
+
+    assert!(matches!(result, Err(Error::InvalidMagic)));
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+/// Creates an empty database and overwrites its `.db` file with `bytes`, starting at `index`.
+fn patch_db_header(db_name: &str, index: usize, bytes: &[u8]) {
+    let _ = fs::remove_dir_all(db_name);
+    drop(Database::create(db_name).unwrap());
+
+    let db_path = Path::new(db_name).join(format!("{}.db", db_name));
+    let mut content = fs::read(&db_path).unwrap();
+    content[index..index + bytes.len()].copy_from_slice(bytes);
+    fs::write(&db_path, &content).unwrap();
+}
+
+#[test]
+fn test_database_open_unsupported_version() {
+    let db_name = "database_version_db";
+    patch_db_header(db_name, 4, &[DB_VERSION + 1]);
+
+    let result = Database::open(db_name);
+
+    assert!(matches!(result, Err(Error::UnsupportedVersion(v)) if v == DB_VERSION + 1));
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_database_open_version_1() {
+    let db_name = "database_version_1_db";
+    // The version of the databases written before the record headers
+    patch_db_header(db_name, 4, &[1]);
+
+    let result = Database::open(db_name);
+
+    assert!(matches!(result, Err(Error::UnsupportedVersion(1))));
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_database_open_unsupported_flags() {
+    let db_name = "database_flags_db";
+    patch_db_header(db_name, 6, &0x8000_0001u32.to_le_bytes());
+
+    let result = Database::open(db_name);
+
+    assert!(matches!(result, Err(Error::UnsupportedFlags(flags)) if flags == 0x8000_0001 & !SUPPORTED_FLAGS));
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_database_open_truncated_header() {
+    let db_name = "database_truncated_header_db";
+    let _ = fs::remove_dir_all(db_name);
+    drop(Database::create(db_name).unwrap());
+
+    let db_path = Path::new(db_name).join(format!("{}.db", db_name));
+    let content = fs::read(&db_path).unwrap();
+    fs::write(&db_path, &content[..DB_HEADER_SIZE - 1]).unwrap();
+
+    assert!(matches!(Database::open(db_name), Err(Error::InvalidMagic)));
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_database_header_flags() {
+    let db_name = "database_header_flags_db";
+    let _ = fs::remove_dir_all(db_name);
+    let header = Database::create(db_name).unwrap().header().unwrap();
+
+    assert_eq!(header.version, DB_VERSION);
+    assert_eq!(header.flags, FLAG_CATALOG);
+    assert!(header.has_flag(0));
+    assert!(header.has_flag(FLAG_CATALOG));
+    assert!(!header.has_flag(0b10));
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_create_table_already_exists() {
+    let db_name = "database_table_exists_db";
+    let db = setup_table(db_name, "items");
+
+    let result = db.create_table("items".to_string(), vec![], vec![], vec![]);
+
+    assert!(matches!(result, Err(Error::AlreadyExists(_))));
+    assert_eq!(db.header().unwrap().num_of_tables, 1);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+
+//read_schema() tests
+fn schema_fields() -> Vec<Field> {
+    vec![
+        Field { name: "id".to_string(), is_oid: true, is_fk: false, has_default: false, on_delete: OnDelete::Restrict, type_: "OID".to_string() },
+        Field { name: "title".to_string(), is_oid: false, is_fk: false, has_default: false, on_delete: OnDelete::Restrict, type_: "String".to_string() },
+        Field { name: "author_id".to_string(), is_oid: false, is_fk: true, has_default: false, on_delete: OnDelete::Restrict, type_: "i32".to_string() },
+    ]
+}
+
+#[test]
+fn test_read_schema_round_trip() {
+    let db_name = "read_schema_db";
+    let _ = fs::remove_dir_all(db_name);
+    let db = Database::create(db_name).unwrap();
+    let references = vec!["authors".to_string(), "publishers".to_string()];
+    db.create_table("books".to_string(), references.clone(), schema_fields(), vec!["summary".to_string()]).unwrap();
+    db.insert_record("books".to_string(), vec![1, 1]).unwrap();
+
+    let schema = db.read_schema("books").unwrap();
+
+    assert_eq!(schema, TableSchema {
+        name: "books".to_string(),
+        last_oid: OID { val: 1 },
+        version: 1,
+        references,
+        fields: schema_fields(),
+        methods: vec!["summary".to_string()],
+    });
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_read_schema_layout() {
+    let db_name = "read_schema_layout_db";
+    let _ = fs::remove_dir_all(db_name);
+    let db = Database::create(db_name).unwrap();
+    db.create_table("books".to_string(), vec!["authors".to_string()], schema_fields()[..2].to_vec(), vec!["summary".to_string()]).unwrap();
+
+    let tbl = fs::read(Path::new(db_name).join("books.tbl")).unwrap();
+
+    // schema_version
+    assert_eq!(&tbl[71..73], &[1, 0]);
+
+    // references_num, then each name left null-padded to 64 bytes
+    assert_eq!(tbl[73], 1);
+    assert_eq!(&tbl[74..131], &[0u8; 57]);
+    assert_eq!(&tbl[131..138], b"authors");
+
+    // length_fields, then the fields, then the methods
+    let fields = [&[2][..], b"id", &[0, 3], b"OID", &[5], b"title", &[0, 6], b"String"].concat();
+    assert_eq!(&tbl[138..140], &(fields.len() as u16).to_le_bytes());
+    assert_eq!(&tbl[140..140 + fields.len()], &fields[..]);
+    assert_eq!(&tbl[140 + fields.len()..148 + fields.len()], b"\x07summary");
+
+    // then the space reserved for new fields
+    let offset_header = read_u32(&tbl, 64) as usize;
+    assert_eq!(offset_header, 148 + fields.len() + SCHEMA_RESERVE);
+    assert!(tbl[148 + fields.len()..offset_header].iter().all(|&b| b == 0));
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_read_schema_corrupt_header() {
+    let db_name = "read_schema_corrupt_db";
+    let _ = fs::remove_dir_all(db_name);
+    let db = Database::create(db_name).unwrap();
+    db.create_table("books".to_string(), vec![], schema_fields(), vec![]).unwrap();
+    drop(db);
+
+    // The name of the first field is not UTF-8
+    let tbl_path = Path::new(db_name).join("books.tbl");
+    let mut tbl = fs::read(&tbl_path).unwrap();
+    tbl[TBL_FIXED_HEADER_SIZE + 1] = 0xFF;
+    fs::write(&tbl_path, &tbl).unwrap();
+
+    let result = Database::open(db_name).unwrap().read_schema("books");
+
+    assert!(matches!(result, Err(Error::Corrupt { offset, .. }) if offset == TBL_FIXED_HEADER_SIZE as u64));
+
+    // References past the end of the header
+    tbl[TBL_FIXED_HEADER_SIZE + 1] = b'i';
+    tbl[73] = 200;
+    fs::write(&tbl_path, &tbl).unwrap();
+
+    let result = Database::open(db_name).unwrap().read_schema("books");
+
+    assert!(matches!(result, Err(Error::Corrupt { .. })));
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_schema_diff() {
+    let schema = TableSchema {
+        name: "books".to_string(),
+        last_oid: OID { val: 0 },
+        version: 1,
+        references: vec![],
+        fields: schema_fields(),
+        methods: vec![],
+    };
+
+    assert!(schema.diff(&schema_fields()).is_empty());
+
+    let mut fields = schema_fields();
+    fields.swap(1, 2);
+    let diff = schema.diff(&fields);
+    assert!(diff.reordered && diff.added.is_empty() && diff.removed.is_empty() && diff.retyped.is_empty());
+
+    let mut fields = schema_fields();
+    fields[1].type_ = "u64".to_string();
+    fields[2].name = "writer_id".to_string();
+    let diff = schema.diff(&fields);
+    assert_eq!(diff.retyped, vec![(schema_fields()[1].clone(), fields[1].clone())]);
+    assert_eq!(diff.added, vec![fields[2].clone()]);
+    assert_eq!(diff.removed, vec![schema_fields()[2].clone()]);
+    assert!(!diff.reordered);
+}
+
+#[test]
+fn test_ensure_table_grows_header() {
+    let db_name = "ensure_table_grow_db";
+    let db = setup_table(db_name, "items");
+    let oids: Vec<OID> = (1..=3u8)
+        .map(|i| db.insert_record("items".to_string(), vec![1, i]).unwrap())
+        .collect();
+    let bucket_path = Path::new(db_name).join("items_bucket.bin");
+    let bucket = fs::read(&bucket_path).unwrap();
+
+    // More new fields than the space reserved in the header
+    let fields: Vec<Field> = (0..10)
+        .map(|i| Field {
+            name: format!("{}{}", "field_".repeat(6), i),
+            is_oid: false,
+            is_fk: false,
+            has_default: true,
+            on_delete: OnDelete::Restrict,
+            type_: "u8".to_string(),
+        })
+        .collect();
+    db.ensure_table("items".to_string(), vec![], fields.clone(), vec![]).unwrap();
+
+    let schema = db.read_schema("items").unwrap();
+    assert_eq!(schema.fields, fields);
+    assert_eq!(schema.version, 2);
+    assert_eq!(schema.last_oid, OID { val: 3 });
+
+    // The index and the data section moved, the bucket didn't change
+    let tbl = fs::read(Path::new(db_name).join("items.tbl")).unwrap();
+    assert!(read_u32(&tbl, 64) as usize > EMPTY_HEADER_SIZE);
+    assert_eq!(fs::read(&bucket_path).unwrap(), bucket);
+
+    for (i, oid) in oids.into_iter().enumerate() {
+        assert_eq!(db.get_record("items".to_string(), oid).unwrap(), Some(vec![1, i as u8 + 1]));
+    }
+    assert_eq!(db.insert_record("items".to_string(), vec![1, 4]).unwrap(), OID { val: 4 });
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_read_schema_missing_table() {
+    let db_name = "read_schema_missing_db";
+    let db = setup_table(db_name, "items");
+
+    assert!(matches!(db.read_schema("books"), Err(Error::NotFound(_))));
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+
+//insert_record() tests
+
+/// Size of the header of a table without fields, methods and references.
+const EMPTY_HEADER_SIZE: usize = TBL_FIXED_HEADER_SIZE + SCHEMA_RESERVE;
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u24(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], 0])
+}
+
+fn setup_table(db_name: &str, table_name: &str) -> Database {
+    let _ = fs::remove_dir_all(db_name);
+    let db = Database::create(db_name).unwrap();
+    db.create_table(table_name.to_string(), vec![], vec![], vec![]).unwrap();
+    db
+}
+
+#[test]
+fn test_insert_record_assigns_oids() {
+    let db_name = "insert_oids_db";
+    let table_name = "items";
+    let db = setup_table(db_name, table_name);
+
+    let first = db.insert_record(table_name.to_string(), vec![1, 7]).unwrap();
+    let second = db.insert_record(table_name.to_string(), vec![2, 8, 9]).unwrap();
+
+    assert_eq!(first.val, 1);
+    assert_eq!(second.val, 2);
+
+    let tbl = fs::read(Path::new(db_name).join(format!("{}.tbl", table_name))).unwrap();
+    let offset_header = read_u32(&tbl, 64) as usize;
+    assert_eq!(offset_header, EMPTY_HEADER_SIZE, "offset_header must point at the end of the header");
+    assert_eq!(read_u24(&tbl, 68), 2, "last_OID must be updated");
+
+    // Both records are appended to the data section, each after its header
+    let data = &tbl[offset_header + INDEX_SIZE..];
+    assert_eq!(data, &[
+        RECORD_LIVE, 1, 0, 0, 2, 0, 0, 0, 2, 0, 0, 0, 1, 7,
+        RECORD_LIVE, 2, 0, 0, 3, 0, 0, 0, 3, 0, 0, 0, 2, 8, 9,
+    ]);
+
+    // The index slot of each OID points to its node, which points to the record
+    let bucket = fs::read(Path::new(db_name).join(format!("{}_bucket.bin", table_name))).unwrap();
+    assert_eq!(bucket.len(), 2 * BUCKET_NODE_SIZE);
+
+    for (oid, address) in [(1u32, 0u32), (2, 14)] {
+        let slot = offset_header + fx_hash16(oid) as usize * 4;
+        let node = read_u32(&tbl, slot) as usize;
+        let node_start = (node - 1) * BUCKET_NODE_SIZE;
+
+        assert_eq!(read_u24(&bucket, node_start), oid);
+        assert_eq!(read_u32(&bucket, node_start + 3), address);
+        assert_eq!(read_u24(&bucket, node_start + 7), 0);
+    }
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_insert_record_chains_collisions() {
+    let db_name = "insert_collisions_db";
+    let table_name = "items";
+    let db = setup_table(db_name, table_name);
+
+    // Next OID with the same slot of OID 1
+    let colliding = (2..MAX_OID).find(|oid| fx_hash16(*oid) == fx_hash16(1)).unwrap();
+
+    db.insert_record(table_name.to_string(), vec![1, 1]).unwrap();
+
+    let tbl_path = Path::new(db_name).join(format!("{}.tbl", table_name));
+    let mut tbl = fs::read(&tbl_path).unwrap();
+    tbl[68..71].copy_from_slice(&(colliding - 1).to_le_bytes()[0..3]);
+    fs::write(&tbl_path, &tbl).unwrap();
+
+    let oid = db.insert_record(table_name.to_string(), vec![1, 2]).unwrap();
+    assert_eq!(oid.val as u32, colliding);
+
+    // The slot points to the newest node, which is chained to the first one
+    let tbl = fs::read(&tbl_path).unwrap();
+    assert_eq!(read_u32(&tbl, EMPTY_HEADER_SIZE + fx_hash16(1) as usize * 4), 2);
+
+    let bucket = fs::read(Path::new(db_name).join(format!("{}_bucket.bin", table_name))).unwrap();
+    assert_eq!(read_u24(&bucket, BUCKET_NODE_SIZE), colliding);
+    assert_eq!(read_u24(&bucket, BUCKET_NODE_SIZE + 7), 1);
+    assert_eq!(read_u24(&bucket, 7), 0);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_insert_record_missing_table() {
+    let db_name = "insert_missing_db";
+    let _ = fs::remove_dir_all(db_name);
+    let db = Database::create(db_name).unwrap();
+
+    let result = db.insert_record("nothing".to_string(), vec![1, 0]);
+
+    assert!(matches!(result, Err(Error::NotFound(_))));
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+
+//get_record() tests
+#[test]
+fn test_get_record() {
+    let db_name = "get_record_db";
+    let table_name = "items";
+    let db = setup_table(db_name, table_name);
+
+    let first = db.insert_record(table_name.to_string(), vec![1, 7]).unwrap();
+    let second = db.insert_record(table_name.to_string(), vec![2, 8, 9]).unwrap();
+
+    assert_eq!(db.get_record(table_name.to_string(), first).unwrap(), Some(vec![1, 7]));
+    assert_eq!(db.get_record(table_name.to_string(), second).unwrap(), Some(vec![2, 8, 9]));
+    assert_eq!(db.get_record(table_name.to_string(), OID { val: 3 }).unwrap(), None);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_get_record_through_collisions() {
+    let db_name = "get_record_collisions_db";
+    let table_name = "items";
+    let db = setup_table(db_name, table_name);
+
+    let colliding = (2..MAX_OID).find(|oid| fx_hash16(*oid) == fx_hash16(1)).unwrap();
+
+    db.insert_record(table_name.to_string(), vec![1, 1]).unwrap();
+
+    let tbl_path = Path::new(db_name).join(format!("{}.tbl", table_name));
+    let mut tbl = fs::read(&tbl_path).unwrap();
+    tbl[68..71].copy_from_slice(&(colliding - 1).to_le_bytes()[0..3]);
+    fs::write(&tbl_path, &tbl).unwrap();
+
+    db.insert_record(table_name.to_string(), vec![1, 2]).unwrap();
+
+    // OID 1 is the tail of the chain, the colliding one is the head
+    assert_eq!(db.get_record(table_name.to_string(), OID { val: 1 }).unwrap(), Some(vec![1, 1]));
+    assert_eq!(db.get_record(table_name.to_string(), OID { val: colliding as i32 }).unwrap(), Some(vec![1, 2]));
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+
+//get_records() tests
+#[derive(Debug, PartialEq)]
+struct Item {
+    oid: OID,
+    bytes: Vec<u8>,
+}
+
+impl Objekt for Item {
+    fn get_table_name() -> String {
+        "items".to_string()
+    }
+
+    fn get_field_types() -> Vec<String> {
+        vec![]
+    }
+
+    fn get_fields() -> Vec<Field> {
+        vec![]
+    }
+
+    fn record_from_bytes(data: Vec<u8>) -> Result<Self, Error> {
+        Ok(Item { oid: OID { val: 0 }, bytes: data })
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.bytes.clone())
+    }
+
+    fn get_oid(&self) -> Option<OID> {
+        Some(self.oid)
+    }
+
+    fn set_oid(&mut self, oid: OID) {
+        self.oid = oid;
+    }
+
+    fn new(db: &Database) -> Result<(), Error> {
+        db.create_table(Self::get_table_name(), vec![], vec![], vec![])
+    }
+}
+
+#[test]
+fn test_get_records_streams_live_records() {
+    let db_name = "get_records_db";
+    let db = setup_table(db_name, "items");
+
+    for bytes in [vec![1, 1], vec![1, 2], vec![1, 3]] {
+        db.insert_record("items".to_string(), bytes).unwrap();
+    }
+
+    // Mark the second record as not live
+    let tbl_path = Path::new(db_name).join("items.tbl");
+    let mut tbl = fs::read(&tbl_path).unwrap();
+    tbl[EMPTY_HEADER_SIZE + INDEX_SIZE + 14] = 0;
+    fs::write(&tbl_path, &tbl).unwrap();
+
+    let mut records = db.get_records::<Item>().unwrap();
+
+    assert_eq!(records.next().map(Result::unwrap), Some(Item { oid: OID { val: 1 }, bytes: vec![1, 1] }));
+    assert_eq!(records.next().map(Result::unwrap), Some(Item { oid: OID { val: 3 }, bytes: vec![1, 3] }));
+    assert!(records.next().is_none());
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_get_records_empty_table() {
+    let db_name = "get_records_empty_db";
+    let db = setup_table(db_name, "items");
+
+    assert_eq!(db.get_records::<Item>().unwrap().count(), 0);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_get_records_truncated_table() {
+    let db_name = "get_records_truncated_db";
+    let db = setup_table(db_name, "items");
+
+    db.insert_record("items".to_string(), vec![1, 1]).unwrap();
+    db.insert_record("items".to_string(), vec![1, 2]).unwrap();
+
+    let tbl_path = Path::new(db_name).join("items.tbl");
+    let tbl = fs::read(&tbl_path).unwrap();
+    fs::write(&tbl_path, &tbl[..tbl.len() - 1]).unwrap();
+
+    let records: Vec<_> = db.get_records::<Item>().unwrap().collect();
+
+    assert_eq!(records.len(), 2);
+    assert!(records[0].is_ok());
+    assert!(matches!(records[1], Err(Error::Corrupt { .. })));
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_get_records_capacity_past_end_of_file() {
+    let db_name = "get_records_capacity_db";
+    let db = setup_table(db_name, "items");
+
+    db.insert_record("items".to_string(), vec![1, 1]).unwrap();
+    db.insert_record("items".to_string(), vec![1, 2]).unwrap();
+
+    // The header of the last record claims almost 4 GiB
+    let tbl_path = Path::new(db_name).join("items.tbl");
+    let mut tbl = fs::read(&tbl_path).unwrap();
+    let record_start = tbl.len() - RECORD_HEADER_SIZE - 2;
+    tbl[record_start + 4..record_start + 12].copy_from_slice(&[0xF0, 0xFF, 0xFF, 0xFF, 0xF0, 0xFF, 0xFF, 0xFF]);
+    fs::write(&tbl_path, &tbl).unwrap();
+
+    let records: Vec<_> = db.get_records::<Item>().unwrap().collect();
+
+    assert_eq!(records.len(), 2);
+    assert!(records[0].is_ok());
+    assert!(matches!(records[1], Err(Error::Corrupt { offset, .. }) if offset == record_start as u64));
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+
+//delete_record() tests
+fn set_last_oid(db_name: &str, table_name: &str, last_oid: u32) {
+    let tbl_path = Path::new(db_name).join(format!("{}.tbl", table_name));
+    let mut tbl = fs::read(&tbl_path).unwrap();
+    tbl[68..71].copy_from_slice(&last_oid.to_le_bytes()[0..3]);
+    fs::write(&tbl_path, &tbl).unwrap();
+}
+
+/// Inserts three records whose OIDs share the same index slot,
+/// returning the OIDs from the head to the tail of the chain.
+fn insert_chain(db_name: &str) -> (Database, [OID; 3]) {
+    let db = setup_table(db_name, "items");
+
+    let mut colliding = (2..MAX_OID).filter(|oid| fx_hash16(*oid) == fx_hash16(1));
+    let second = colliding.next().unwrap();
+    let third = colliding.next().unwrap();
+
+    let tail = db.insert_record("items".to_string(), vec![1, 1]).unwrap();
+    set_last_oid(db_name, "items", second - 1);
+    let middle = db.insert_record("items".to_string(), vec![1, 2]).unwrap();
+    set_last_oid(db_name, "items", third - 1);
+    let head = db.insert_record("items".to_string(), vec![1, 3]).unwrap();
+
+    (db, [head, middle, tail])
+}
+
+fn assert_deleted(db: &Database, chain: [OID; 3], deleted: usize) {
+    assert!(db.delete_record("items".to_string(), chain[deleted]).unwrap());
+
+    for (i, oid) in chain.iter().enumerate() {
+        let record = db.get_record("items".to_string(), *oid).unwrap();
+        assert_eq!(record.is_none(), i == deleted, "wrong lookup of OID {}", oid.val);
+    }
+
+    let scanned: Vec<OID> = db.get_records::<Item>().unwrap()
+        .map(|item| item.unwrap().oid)
+        .collect();
+    assert_eq!(scanned.len(), 2);
+    assert!(!scanned.contains(&chain[deleted]));
+
+    // A second delete finds nothing
+    assert!(!db.delete_record("items".to_string(), chain[deleted]).unwrap());
+}
+
+#[test]
+fn test_delete_record_head_of_chain() {
+    let db_name = "delete_head_db";
+    let (db, chain) = insert_chain(db_name);
+
+    assert_deleted(&db, chain, 0);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_delete_record_middle_of_chain() {
+    let db_name = "delete_middle_db";
+    let (db, chain) = insert_chain(db_name);
+
+    assert_deleted(&db, chain, 1);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_delete_record_tail_of_chain() {
+    let db_name = "delete_tail_db";
+    let (db, chain) = insert_chain(db_name);
+
+    assert_deleted(&db, chain, 2);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_delete_record_clears_slot() {
+    let db_name = "delete_slot_db";
+    let db = setup_table(db_name, "items");
+
+    let oid = db.insert_record("items".to_string(), vec![1, 1]).unwrap();
+    assert!(db.delete_record("items".to_string(), oid).unwrap());
+
+    let tbl = fs::read(Path::new(db_name).join("items.tbl")).unwrap();
+    assert_eq!(read_u32(&tbl, EMPTY_HEADER_SIZE + fx_hash16(1) as usize * 4), 0);
+    assert_eq!(tbl[EMPTY_HEADER_SIZE + INDEX_SIZE], RECORD_DELETED);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+
+//update_record() tests
+#[test]
+fn test_update_record_in_place() {
+    let db_name = "update_in_place_db";
+    let db = setup_table(db_name, "items");
+
+    let oid = db.insert_record("items".to_string(), vec![3, 1, 2, 3]).unwrap();
+    db.insert_record("items".to_string(), vec![1, 9]).unwrap();
+    let tbl_len = fs::metadata(Path::new(db_name).join("items.tbl")).unwrap().len();
+
+    assert!(db.update_record("items".to_string(), oid, vec![2, 4, 5]).unwrap());
+
+    assert_eq!(db.get_record("items".to_string(), oid).unwrap(), Some(vec![2, 4, 5]));
+    assert_eq!(fs::metadata(Path::new(db_name).join("items.tbl")).unwrap().len(), tbl_len);
+
+    // The unused capacity is skipped by the scan
+    let scanned: Vec<Item> = db.get_records::<Item>().unwrap().map(Result::unwrap).collect();
+    assert_eq!(scanned, [
+        Item { oid, bytes: vec![2, 4, 5] },
+        Item { oid: OID { val: 2 }, bytes: vec![1, 9] },
+    ]);
+
+    // It can grow back up to its capacity
+    assert!(db.update_record("items".to_string(), oid, vec![3, 6, 7, 8]).unwrap());
+    assert_eq!(db.get_record("items".to_string(), oid).unwrap(), Some(vec![3, 6, 7, 8]));
+    assert_eq!(fs::metadata(Path::new(db_name).join("items.tbl")).unwrap().len(), tbl_len);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_update_record_relocation() {
+    let db_name = "update_relocation_db";
+    let (db, chain) = insert_chain(db_name);
+
+    let bigger = vec![5, 1, 2, 3, 4, 5];
+    assert!(db.update_record("items".to_string(), chain[1], bigger.clone()).unwrap());
+
+    // Same OID, the chain is still intact
+    assert_eq!(db.get_record("items".to_string(), chain[0]).unwrap(), Some(vec![1, 3]));
+    assert_eq!(db.get_record("items".to_string(), chain[1]).unwrap(), Some(bigger.clone()));
+    assert_eq!(db.get_record("items".to_string(), chain[2]).unwrap(), Some(vec![1, 1]));
+
+    // The old copy is gone from the scan, the new one is at the end
+    let scanned: Vec<Item> = db.get_records::<Item>().unwrap().map(Result::unwrap).collect();
+    assert_eq!(scanned.len(), 3);
+    assert_eq!(scanned[2], Item { oid: chain[1], bytes: bigger });
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_update_record_missing() {
+    let db_name = "update_missing_db";
+    let db = setup_table(db_name, "items");
+
+    let oid = db.insert_record("items".to_string(), vec![1, 1]).unwrap();
+    db.delete_record("items".to_string(), oid).unwrap();
+
+    assert!(!db.update_record("items".to_string(), oid, vec![1, 2]).unwrap());
+    assert!(!db.update_record("items".to_string(), OID { val: 7 }, vec![1, 2]).unwrap());
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+//reinitialize_table() tests
+#[test]
+fn test_reinitialize_table_replaces_files() {
+    let db_name = "reinitialize_db";
+    let db = setup_table(db_name, "items");
+    db.create_table("others".to_string(), vec![], vec![], vec![]).unwrap();
+    db.insert_record("items".to_string(), vec![1, 5]).unwrap();
+    db.insert_record("items".to_string(), vec![1, 6]).unwrap();
+
+    let backup = db.reinitialize_table("items".to_string(), vec!["others".to_string()], schema_fields(), vec![]).unwrap();
+
+    // The new table is empty, with the new schema
+    let schema = db.read_schema("items").unwrap();
+    assert_eq!(schema.fields, schema_fields());
+    assert_eq!(schema.references, vec!["others".to_string()]);
+    assert_eq!(schema.version, 2);
+    assert_eq!(db.get_record("items".to_string(), OID { val: 1 }).unwrap(), None);
+    assert_eq!(fs::metadata(Path::new(db_name).join("items_bucket.bin")).unwrap().len(), 0);
+
+    // The OIDs of the old records are not given again
+    assert_eq!(schema.last_oid, OID { val: 2 });
+    assert_eq!(db.insert_record("items".to_string(), vec![1, 7]).unwrap(), OID { val: 3 });
+
+    // The table count is the same
+    assert_eq!(db.header().unwrap().num_of_tables, 2);
+    assert_eq!(Database::open(db_name).unwrap().header().unwrap().num_of_tables, 2);
+
+    // The old files are in the backup
+    assert!(backup.starts_with(Path::new(db_name).join(BACKUP_DIR)));
+    assert!(backup.file_name().unwrap().to_str().unwrap().starts_with("items-"));
+    let old = fs::read(backup.join("items.tbl")).unwrap();
+    assert_eq!(read_u24(&old, 68), 2);
+    assert_eq!(fs::metadata(backup.join("items_bucket.bin")).unwrap().len(), 2 * BUCKET_NODE_SIZE as u64);
+
+    // No temporary files are left
+    assert!(!Path::new(db_name).join("items.tbl.reinit").exists());
+    assert!(!Path::new(db_name).join("items_bucket.bin.reinit").exists());
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_reinitialize_table_twice_keeps_both_backups() {
+    let db_name = "reinitialize_twice_db";
+    let db = setup_table(db_name, "items");
+
+    let first = db.reinitialize_table("items".to_string(), vec![], vec![], vec![]).unwrap();
+    let second = db.reinitialize_table("items".to_string(), vec![], vec![], vec![]).unwrap();
+
+    assert_ne!(first, second);
+    assert!(first.join("items.tbl").exists());
+    assert!(second.join("items.tbl").exists());
+    assert_eq!(db.read_schema("items").unwrap().version, 3);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_reinitialize_table_missing() {
+    let db_name = "reinitialize_missing_db";
+    let db = setup_table(db_name, "items");
+
+    let result = db.reinitialize_table("missing".to_string(), vec![], vec![], vec![]);
+
+    assert!(matches!(result, Err(Error::NotFound(_))));
+    assert!(!Path::new(db_name).join(BACKUP_DIR).exists());
+    assert_eq!(db.header().unwrap().num_of_tables, 1);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+//drop_table() / rename_table() tests
+#[test]
+fn test_drop_table() {
+    let db_name = "drop_table_db";
+    let db = setup_table(db_name, "items");
+    db.create_table("others".to_string(), vec![], vec![], vec![]).unwrap();
+    db.insert_record("items".to_string(), vec![1, 5]).unwrap();
+
+    db.drop_table("items").unwrap();
+
+    assert!(!Path::new(db_name).join("items.tbl").exists());
+    assert!(!Path::new(db_name).join("items_bucket.bin").exists());
+    assert!(matches!(db.read_schema("items"), Err(Error::NotFound(_))));
+    assert_eq!(db.header().unwrap().num_of_tables, 1);
+    assert_eq!(Database::open(db_name).unwrap().header().unwrap().num_of_tables, 1);
+
+    // The name can be used again
+    db.create_table("items".to_string(), vec![], vec![], vec![]).unwrap();
+    assert_eq!(db.get_record("items".to_string(), OID { val: 1 }).unwrap(), None);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_drop_table_referenced() {
+    let db_name = "drop_table_referenced_db";
+    let db = setup_table(db_name, "authors");
+    db.create_table("books".to_string(), vec!["authors".to_string()], schema_fields(), vec![]).unwrap();
+
+    match db.drop_table("authors") {
+        Err(Error::Referenced { table, by }) => {
+            assert_eq!(table, "authors");
+            assert_eq!(by, "books");
+        },
+        other => panic!("expected a referenced table, got {:?}", other),
+    }
+    assert!(Path::new(db_name).join("authors.tbl").exists());
+    assert_eq!(db.header().unwrap().num_of_tables, 2);
+
+    // Once the referencing table is gone it can be dropped
+    db.drop_table("books").unwrap();
+    db.drop_table("authors").unwrap();
+    assert_eq!(db.header().unwrap().num_of_tables, 0);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_drop_table_missing() {
+    let db_name = "drop_table_missing_db";
+    let db = setup_table(db_name, "items");
+
+    assert!(matches!(db.drop_table("missing"), Err(Error::NotFound(_))));
+    assert_eq!(db.header().unwrap().num_of_tables, 1);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_rename_table() {
+    let db_name = "rename_table_db";
+    let db = setup_table(db_name, "authors");
+    db.create_table("books".to_string(), vec!["authors".to_string()], schema_fields(), vec![]).unwrap();
+    let oid = db.insert_record("authors".to_string(), vec![1, 5]).unwrap();
+
+    db.rename_table("authors", "writers").unwrap();
+
+    let dir = Path::new(db_name);
+    assert!(!dir.join("authors.tbl").exists());
+    assert!(!dir.join("authors_bucket.bin").exists());
+    assert!(dir.join("writers_bucket.bin").exists());
+
+    // The name in the header is the new one, padded to 64 bytes
+    let tbl = fs::read(dir.join("writers.tbl")).unwrap();
+    assert_eq!(&tbl[..64 - 7], &[0u8; 64 - 7][..]);
+    assert_eq!(&tbl[64 - 7..64], b"writers");
+
+    assert_eq!(db.get_record("writers".to_string(), oid).unwrap(), Some(vec![1, 5]));
+    assert_eq!(db.read_schema("writers").unwrap().name, "writers");
+    assert_eq!(db.read_schema("books").unwrap().references, vec!["writers".to_string()]);
+    assert_eq!(db.header().unwrap().num_of_tables, 2);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_rename_table_errors() {
+    let db_name = "rename_table_errors_db";
+    let db = setup_table(db_name, "items");
+    db.create_table("others".to_string(), vec![], vec![], vec![]).unwrap();
+
+    assert!(matches!(db.rename_table("missing", "new"), Err(Error::NotFound(_))));
+    assert!(matches!(db.rename_table("items", "others"), Err(Error::AlreadyExists(_))));
+    assert!(matches!(db.rename_table("items", &"a".repeat(65)), Err(Error::NameTooLong(_))));
+
+    // Nothing changed
+    assert_eq!(db.read_schema("items").unwrap().name, "items");
+    assert!(Path::new(db_name).join("items_bucket.bin").exists());
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_rename_table_interrupted() {
+    let db_name = "rename_table_interrupted_db";
+    let db = setup_table(db_name, "authors");
+    db.create_table("books".to_string(), vec!["authors".to_string()], schema_fields(), vec![]).unwrap();
+    let oid = db.insert_record("authors".to_string(), vec![1, 5]).unwrap();
+
+    // The header of authors is written and its files are renamed, the header of books fails
+    WRITES_BEFORE_FAILURE.with(|writes| writes.set(Some(1)));
+    assert!(matches!(db.rename_table("authors", "writers"), Err(Error::Io(_))));
+
+    let dir = Path::new(db_name);
+    assert!(dir.join("authors.rename").exists());
+    assert!(dir.join("writers.tbl").exists());
+    assert_eq!(db.read_schema("books").unwrap().references, vec!["authors".to_string()]);
+    drop(db);
+
+    // open() finishes the rename
+    let db = Database::open(db_name).unwrap();
+
+    assert!(!dir.join("authors.rename").exists());
+    assert_eq!(db.get_record("writers".to_string(), oid).unwrap(), Some(vec![1, 5]));
+    assert_eq!(db.read_schema("books").unwrap().references, vec!["writers".to_string()]);
+    assert_eq!(db.tables().unwrap().iter().map(|entry| entry.name.as_str()).collect::<Vec<_>>(), vec!["writers", "books"]);
+    assert!(db.check_catalog().unwrap().is_empty());
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_drop_table_interrupted() {
+    let db_name = "drop_table_interrupted_db";
+    let db = setup_table(db_name, "items");
+    db.create_table("others".to_string(), vec![], vec![], vec![]).unwrap();
+
+    let dir = Path::new(db_name);
+    let tbl = fs::read(dir.join("items.tbl")).unwrap();
+    db.drop_table("items").unwrap();
+    drop(db);
+
+    // A crash after the catalog was written, before the files were deleted
+    fs::write(dir.join("items.tbl"), &tbl).unwrap();
+    fs::write(dir.join("items_bucket.bin"), b"").unwrap();
+    fs::write(dir.join("items.drop"), b"").unwrap();
+
+    let db = Database::open(db_name).unwrap();
+
+    assert!(!dir.join("items.tbl").exists());
+    assert!(!dir.join("items_bucket.bin").exists());
+    assert!(!dir.join("items.drop").exists());
+    assert_eq!(db.header().unwrap().num_of_tables, 1);
+    assert!(db.check_catalog().unwrap().is_empty());
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+//tables() / check_catalog() tests
+#[test]
+fn test_tables_catalog() {
+    let db_name = "catalog_db";
+    let _ = fs::remove_dir_all(db_name);
+    let db = Database::create(db_name).unwrap();
+    assert!(db.tables().unwrap().is_empty());
+
+    db.create_table("authors".to_string(), vec![], vec![], vec![]).unwrap();
+    db.create_table("books".to_string(), vec!["authors".to_string()], schema_fields(), vec![]).unwrap();
+    db.create_table("shelves".to_string(), vec![], vec![], vec![]).unwrap();
+    db.drop_table("shelves").unwrap();
+    db.rename_table("authors", "writers").unwrap();
+    db.create_table("shelves".to_string(), vec![], vec![], vec![]).unwrap();
+    drop(db);
+
+    let db = Database::open(db_name).unwrap();
+    let tables = db.tables().unwrap();
+    let books = db.read_schema("books").unwrap();
+
+    assert_eq!(tables.len(), 3);
+    assert_eq!(tables[0], TableEntry {
+        id: 1,
+        name: "writers".to_string(),
+        schema_hash: db.read_schema("writers").unwrap().schema_hash(),
+        file_name: "writers.tbl".to_string(),
+    });
+    assert_eq!((tables[1].id, tables[1].name.as_str()), (2, "books"));
+    assert_eq!(tables[1].schema_hash, books.schema_hash());
+    assert_eq!((tables[2].id, tables[2].name.as_str()), (3, "shelves"));
+    assert_ne!(tables[0].schema_hash, tables[1].schema_hash);
+    assert_eq!(db.header().unwrap().num_of_tables, 3);
+    assert!(db.check_catalog().unwrap().is_empty());
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_catalog_write_failure_keeps_old_catalog() {
+    let db_name = "catalog_write_failure_db";
+    let db = setup_table(db_name, "items");
+    let db_path = Path::new(db_name).join(format!("{}.db", db_name));
+    let new_path = Path::new(db_name).join(format!("{}.db.new", db_name));
+    let before = fs::read(&db_path).unwrap();
+
+    // The new copy of the .db file can't be created
+    fs::create_dir(&new_path).unwrap();
+    let result = db.create_table("others".to_string(), vec![], vec![], vec![]);
+
+    assert!(matches!(result, Err(Error::Io(_))));
+    assert_eq!(fs::read(&db_path).unwrap(), before);
+    drop(db);
+
+    fs::remove_dir(&new_path).unwrap();
+    let db = Database::open(db_name).unwrap();
+    assert_eq!(db.tables().unwrap().iter().map(|entry| entry.name.as_str()).collect::<Vec<_>>(), vec!["items"]);
+    assert_eq!(db.check_catalog().unwrap().orphaned, vec!["others.tbl".to_string()]);
+
+    // The next write replaces the file again
+    db.create_table("shelves".to_string(), vec![], vec![], vec![]).unwrap();
+    assert!(!new_path.exists());
+    drop(db);
+
+    let db = Database::open(db_name).unwrap();
+    assert_eq!(db.header().unwrap().num_of_tables, 2);
+    drop(db);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_tables_schema_hash_follows_schema() {
+    let db_name = "catalog_hash_db";
+    let db = setup_table(db_name, "items");
+    let created = db.tables().unwrap()[0].schema_hash;
+
+    db.reinitialize_table("items".to_string(), vec![], schema_fields(), vec![]).unwrap();
+
+    let reinitialized = db.tables().unwrap()[0].schema_hash;
+    assert_ne!(created, reinitialized);
+    assert_eq!(reinitialized, db.read_schema("items").unwrap().schema_hash());
+    assert!(db.check_catalog().unwrap().is_empty());
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_check_catalog() {
+    let db_name = "catalog_check_db";
+    let db = setup_table(db_name, "items");
+    db.create_table("others".to_string(), vec![], vec![], vec![]).unwrap();
+    db.create_table("changed".to_string(), vec![], vec![], vec![]).unwrap();
+    let dir = Path::new(db_name);
+
+    fs::remove_file(dir.join("others.tbl")).unwrap();
+    fs::copy(dir.join("items.tbl"), dir.join("stray.tbl")).unwrap();
+
+    // A schema written to the .tbl file behind the back of the catalog
+    let mut tbl = fs::read(dir.join("changed.tbl")).unwrap();
+    let schema = TableSchema { fields: schema_fields(), ..parse_table_header("changed", &tbl[..EMPTY_HEADER_SIZE]).unwrap() };
+    let header = table_header_bytes(&schema, EMPTY_HEADER_SIZE).unwrap();
+    tbl[..EMPTY_HEADER_SIZE].copy_from_slice(&header);
+    fs::write(dir.join("changed.tbl"), tbl).unwrap();
+
+    let check = db.check_catalog().unwrap();
+
+    assert_eq!(check, CatalogCheck {
+        missing: vec!["others".to_string()],
+        orphaned: vec!["stray.tbl".to_string()],
+        changed: vec!["changed".to_string()],
+    });
+    assert!(!check.is_empty());
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_open_refuses_header_without_catalog() {
+    let db_name = "catalog_missing_db";
+    patch_db_header(db_name, 6, &0u32.to_le_bytes());
+
+    let result = Database::open(db_name);
+
+    assert!(matches!(result, Err(Error::CorruptCatalog { offset }) if offset == DB_HEADER_SIZE as u64));
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_open_corrupt_catalog() {
+    let db_name = "catalog_corrupt_db";
+    let db = setup_table(db_name, "items");
+    drop(db);
+
+    let db_path = Path::new(db_name).join(format!("{}.db", db_name));
+    let content = fs::read(&db_path).unwrap();
+    fs::write(&db_path, &content[..content.len() - 1]).unwrap();
+
+    // The file name of the only entry is cut
+    let offset = (DB_HEADER_SIZE + 4 + 64 + 8 + 1) as u64;
+    assert!(matches!(Database::open(db_name), Err(Error::CorruptCatalog { offset: o }) if o == offset));
+
+    fs::remove_dir_all(db_name).unwrap();
+}
@@ -1,4 +1,4 @@
-use super::super::{storage_engine::file_manager::*, support_mods::{field::*, support_functions::*}};
+use super::super::{error::Error, storage_engine::{database::Database, file_manager::*}, support_mods::field::*};
 use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
@@ -20,9 +20,9 @@ fn test_create_db_success() {
     // Check if the .db file exists
     assert!(db_file_path.exists(), "Database file was not created");
 
-    // Clean up
+    // Clean up, the directory has the log of the database too
     let _ = fs::remove_file(&db_file_path);
-    let _ = fs::remove_dir(&db_dir_path);
+    let _ = fs::remove_dir_all(&db_dir_path);
 }
 
 #[test]
@@ -41,9 +41,9 @@ fn test_create_db_already_exists() {
     let result = create_db(db_name.to_string());
     assert!(result.is_err(), "Second database creation should fail");
 
-    // Clean up
+    // Clean up, the directory has the log of the database too
     let _ = fs::remove_file(&db_file_path);
-    let _ = fs::remove_dir(&db_dir_path);
+    let _ = fs::remove_dir_all(&db_dir_path);
 }
 
 //create_table() tests
@@ -58,11 +58,19 @@ fn test_create_table_success() {
     let fields = vec![
         Field {
             name: "id".to_string(),
-            is_oid: true
+            is_oid: true,
+            is_fk: false,
+            has_default: false,
+            on_delete: OnDelete::Restrict,
+            type_: "OID".to_string()
         },
         Field {
             name: "role_id".to_string(),
-            is_oid: false
+            is_oid: false,
+            is_fk: true,
+            has_default: false,
+            on_delete: OnDelete::Restrict,
+            type_: "u32".to_string()
         },
     ];
     let methods = vec!["find_all".to_string()];
@@ -70,6 +78,7 @@ fn test_create_table_success() {
     let result = create_table(
         table_name.to_string(),
         db_name.to_string(),
+        refs,
         fields,
         methods,
     );
@@ -80,6 +89,11 @@ fn test_create_table_success() {
     assert!(base.join(format!("{}.tbl", table_name)).exists());
     assert!(base.join(format!("{}_bucket.bin", table_name)).exists());
 
+    let schema = Database::open(db_name).unwrap().read_schema(table_name).unwrap();
+    assert_eq!(schema.references, vec!["roles".to_string()]);
+    assert_eq!(schema.fields.len(), 2);
+    assert_eq!(schema.methods, vec!["find_all".to_string()]);
+
     // Cleanup
     fs::remove_file(base.join(format!("{}.tbl", table_name))).unwrap();
     fs::remove_file(base.join(format!("{}_bucket.bin", table_name))).unwrap();
@@ -94,10 +108,10 @@ fn test_create_table_missing_db() {
         "nonexistent".to_string(),
         vec![],
         vec![],
+        vec![],
     );
 
-    assert!(result.is_err());
-    assert!(result.unwrap_err().contains("does not exist"));
+    assert!(matches!(result, Err(Error::NotFound(_))));
 }
 
 #[test]
@@ -109,7 +123,11 @@ fn test_create_table_too_many_tables() {
 
     let db_path = Path::new(db_name).join(format!("{}.db", db_name));
     let mut content = fs::read(&db_path).unwrap();
-    content[4] = 255; // simulate max tables reached
+    content[5] = 255; // simulate max tables reached (byte 4 is the version)
+    let catalog: Vec<TableEntry> = (1..=255)
+        .map(|id| TableEntry { id, name: format!("t{}", id), schema_hash: 0, file_name: format!("t{}.tbl", id) })
+        .collect();
+    content.extend_from_slice(&catalog_bytes(&catalog).unwrap());
     fs::write(&db_path, &content).unwrap();
 
     let result = create_table(
@@ -117,10 +135,10 @@ fn test_create_table_too_many_tables() {
         db_name.to_string(),
         vec![],
         vec![],
+        vec![],
     );
 
-    assert!(result.is_err());
-    assert_eq!(result.unwrap_err(), "Maximum number of tables reached (255)");
+    assert!(matches!(result, Err(Error::TableLimit)));
 
     // Cleanup
     fs::remove_file(db_path).unwrap();
@@ -139,12 +157,76 @@ fn test_table_name_too_long() {
         db_name.to_string(),
         vec![],
         vec![],
+        vec![],
     );
 
-    assert!(result.is_err());
-    assert!(result.unwrap_err().contains("too long"));
+    assert!(matches!(result, Err(Error::NameTooLong(_))));
 
     // Cleanup
     fs::remove_file(Path::new(db_name).join(format!("{}.db", db_name))).unwrap();
     fs::remove_dir_all(db_name).unwrap();
-}
\ No newline at end of file
+}
+
+//delete_db() tests
+#[test]
+fn test_delete_db_removes_directory() {
+    let db_name = "test_db_delete";
+    let _ = fs::remove_dir_all(db_name);
+
+    let db = Database::create(db_name).unwrap();
+    db.create_table("users".to_string(), vec![], vec![], vec![]).unwrap();
+    db.create_table("roles".to_string(), vec![], vec![], vec![]).unwrap();
+    db.insert_record("users".to_string(), vec![1, 5]).unwrap();
+    db.reinitialize_table("roles".to_string(), vec![], vec![], vec![]).unwrap();
+    drop(db);
+
+    assert!(delete_db(db_name.to_string()).is_ok());
+    assert!(!Path::new(db_name).exists());
+}
+
+#[test]
+fn test_delete_db_missing() {
+    let result = delete_db("test_db_delete_missing".to_string());
+
+    assert!(matches!(result, Err(Error::NotFound(_))));
+}
+
+#[test]
+fn test_delete_db_refuses_open_database() {
+    let db_name = "test_db_delete_open";
+    let _ = fs::remove_dir_all(db_name);
+
+    let db = Database::create(db_name).unwrap();
+    db.create_table("users".to_string(), vec![], vec![], vec![]).unwrap();
+    let clone = db.clone();
+    drop(db);
+
+    // A clone keeps the database open
+    let result = delete_db(db_name.to_string());
+    assert!(matches!(result, Err(Error::InUse(_))));
+    assert!(Path::new(db_name).join("users.tbl").exists());
+
+    // A handle opened through another path is the same database
+    drop(clone);
+    let other = Database::open(PathBuf::from(".").join(db_name)).unwrap();
+    assert!(matches!(delete_db(db_name.to_string()), Err(Error::InUse(_))));
+
+    drop(other);
+    assert!(delete_db(db_name.to_string()).is_ok());
+    assert!(!Path::new(db_name).exists());
+}
+
+#[test]
+fn test_delete_db_not_a_database() {
+    let db_name = "test_db_delete_invalid";
+    let _ = fs::remove_dir_all(db_name);
+    fs::create_dir(db_name).unwrap();
+    fs::write(Path::new(db_name).join(format!("{}.db", db_name)), b"not a database").unwrap();
+
+    let result = delete_db(db_name.to_string());
+
+    assert!(matches!(result, Err(Error::InvalidMagic)));
+    assert!(Path::new(db_name).exists());
+
+    fs::remove_dir_all(db_name).unwrap();
+}
@@ -0,0 +1,28 @@
+use super::super::traits::from_bytes::*;
+
+#[test]
+fn test_from_bytes_wrong_length() {
+    assert_eq!(i32::from_bytes(&[1, 0, 0]), Err(DecodeError::Length { expected: 4, found: 3 }));
+    assert_eq!(u8::from_bytes(&[]), Err(DecodeError::Length { expected: 1, found: 0 }));
+    assert_eq!(bool::from_bytes(&[1, 1]), Err(DecodeError::Length { expected: 1, found: 2 }));
+}
+
+#[test]
+fn test_from_bytes_invalid_utf8() {
+    assert_eq!(String::from_bytes(&[0xC3, 0x28]), Err(DecodeError::InvalidUtf8));
+}
+
+#[test]
+fn test_from_bytes_invalid_char() {
+    // Surrogates are not Unicode scalar values
+    assert_eq!(char::from_bytes(&0xD800u32.to_le_bytes()), Err(DecodeError::InvalidChar(0xD800)));
+    assert_eq!(char::from_bytes(&0x11_0000u32.to_le_bytes()), Err(DecodeError::InvalidChar(0x11_0000)));
+}
+
+#[test]
+fn test_from_bytes_valid_values() {
+    assert_eq!(i16::from_bytes(&[0xC0, 0xF9]), Ok(-1600));
+    assert_eq!(bool::from_bytes(&[0]), Ok(false));
+    assert_eq!(char::from_bytes(&('ß' as u32).to_le_bytes()), Ok('ß'));
+    assert_eq!(String::from_bytes(b"objektDB"), Ok("objektDB".to_string()));
+}
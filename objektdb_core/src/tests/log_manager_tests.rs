@@ -0,0 +1,244 @@
+use super::super::{error::Error, storage_engine::{database::Database, file_manager::{TableFiles, WRITES_BEFORE_FAILURE}, log_manager::{LogManager, PageWrite}}, support_mods::field::OID};
+use std::fs;
+use std::path::Path;
+
+/// Creates a database with the table `items` and one record in it.
+fn setup(db_name: &str) {
+    let _ = fs::remove_dir_all(db_name);
+
+    let db = Database::create(db_name).unwrap();
+    db.create_table("items".to_string(), vec![], vec![], vec![]).unwrap();
+    db.insert_record("items".to_string(), vec![1, 5]).unwrap();
+}
+
+/// The content of the `.tbl` and `_bucket.bin` files of `items`.
+fn table_files(db_name: &str) -> (Vec<u8>, Vec<u8>) {
+    let dir = Path::new(db_name);
+
+    (fs::read(dir.join("items.tbl")).unwrap(), fs::read(dir.join("items_bucket.bin")).unwrap())
+}
+
+fn restore(db_name: &str, files: &(Vec<u8>, Vec<u8>)) {
+    let dir = Path::new(db_name);
+
+    fs::write(dir.join("items.tbl"), &files.0).unwrap();
+    fs::write(dir.join("items_bucket.bin"), &files.1).unwrap();
+}
+
+fn wal_path(db_name: &str) -> std::path::PathBuf {
+    Path::new(db_name).join(format!("{}.wal", db_name))
+}
+
+/// Leaves two records in the pending writes of `table`: the record of OID 1 moved
+/// to the end of the data section, and a new one.
+fn relocate_and_insert(table: &mut TableFiles) -> Vec<PageWrite> {
+    assert!(table.update(OID { val: 1 }, &[3, 1, 2, 3]).unwrap());
+    table.insert(&[1, 9]).unwrap();
+
+    table.take_pending()
+}
+
+/// Logs writes to `items` as a transaction and applies them, like a commit that
+/// crashed before emptying the log.
+fn log_and_apply(log: &mut LogManager, table: &mut TableFiles, writes: Vec<PageWrite>) {
+    log.append(&[("items".to_string(), writes.clone())]).unwrap();
+    table.apply(&writes).unwrap();
+}
+
+#[test]
+fn test_commit_empties_log() {
+    let db_name = "log_commit_db";
+    setup(db_name);
+
+    let db = Database::open(db_name).unwrap();
+    let oid = db.insert_record("items".to_string(), vec![1, 6]).unwrap();
+    assert!(db.update_record("items".to_string(), oid, vec![2, 6, 6]).unwrap());
+    assert!(db.delete_record("items".to_string(), OID { val: 1 }).unwrap());
+
+    assert_eq!(fs::metadata(wal_path(db_name)).unwrap().len(), 0);
+    drop(db);
+
+    let db = Database::open(db_name).unwrap();
+    assert_eq!(db.get_record("items".to_string(), oid).unwrap(), Some(vec![2, 6, 6]));
+    assert_eq!(db.get_record("items".to_string(), OID { val: 1 }).unwrap(), None);
+    drop(db);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_recover_log_truncated_at_every_offset() {
+    let db_name = "log_truncated_db";
+    setup(db_name);
+    let dir = Path::new(db_name);
+
+    let before = table_files(db_name);
+
+    // Two transactions: an insert, then a relocation and an insert
+    let mut table = TableFiles::open(dir, "items").unwrap();
+    let mut log = LogManager::open(dir, db_name).unwrap();
+
+    table.insert(&[1, 7]).unwrap();
+    let writes = table.take_pending();
+    log_and_apply(&mut log, &mut table, writes);
+    let first_end = fs::metadata(wal_path(db_name)).unwrap().len() as usize;
+    let middle = table_files(db_name);
+
+    let writes = relocate_and_insert(&mut table);
+    log_and_apply(&mut log, &mut table, writes);
+    let after = table_files(db_name);
+
+    drop(table);
+    drop(log);
+    let full_log = fs::read(wal_path(db_name)).unwrap();
+    assert!(before != middle && middle != after);
+
+    // A crash while the log was written: only the committed transactions are applied
+    for cut in 0..=full_log.len() {
+        restore(db_name, &before);
+        fs::write(wal_path(db_name), &full_log[..cut]).unwrap();
+
+        drop(Database::open(db_name).unwrap());
+
+        let expected = if cut == full_log.len() {
+            &after
+        } else if cut >= first_end {
+            &middle
+        } else {
+            &before
+        };
+        assert!(table_files(db_name) == *expected, "wrong recovery of the log cut at {}", cut);
+        assert_eq!(fs::metadata(wal_path(db_name)).unwrap().len(), 0);
+    }
+
+    let db = Database::open(db_name).unwrap();
+    assert_eq!(db.get_record("items".to_string(), OID { val: 1 }).unwrap(), Some(vec![3, 1, 2, 3]));
+    assert_eq!(db.get_record("items".to_string(), OID { val: 2 }).unwrap(), Some(vec![1, 7]));
+    assert_eq!(db.get_record("items".to_string(), OID { val: 3 }).unwrap(), Some(vec![1, 9]));
+    assert_eq!(db.count_records("items".to_string()).unwrap(), 3);
+    drop(db);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_recover_partially_applied_transaction() {
+    let db_name = "log_partial_db";
+    setup(db_name);
+    let dir = Path::new(db_name);
+
+    let before = table_files(db_name);
+
+    let mut table = TableFiles::open(dir, "items").unwrap();
+    let mut log = LogManager::open(dir, db_name).unwrap();
+    let writes = relocate_and_insert(&mut table);
+    log_and_apply(&mut log, &mut table, writes.clone());
+    let after = table_files(db_name);
+
+    drop(log);
+    let full_log = fs::read(wal_path(db_name)).unwrap();
+
+    // A crash while the committed writes were applied to the table
+    for applied in 0..=writes.len() {
+        restore(db_name, &before);
+        table.apply(&writes[..applied]).unwrap();
+        fs::write(wal_path(db_name), &full_log).unwrap();
+
+        drop(Database::open(db_name).unwrap());
+
+        assert!(table_files(db_name) == after, "wrong recovery after {} writes", applied);
+    }
+
+    drop(table);
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_recover_discards_corrupt_transaction() {
+    let db_name = "log_corrupt_db";
+    setup(db_name);
+    let dir = Path::new(db_name);
+
+    let before = table_files(db_name);
+
+    let mut table = TableFiles::open(dir, "items").unwrap();
+    let mut log = LogManager::open(dir, db_name).unwrap();
+    let writes = relocate_and_insert(&mut table);
+    log.append(&[("items".to_string(), writes)]).unwrap();
+    drop(table);
+    drop(log);
+
+    // A write entry whose bytes don't match the checksum of the commit
+    let mut content = fs::read(wal_path(db_name)).unwrap();
+    let last_write = content.len() - 13 - 1;
+    content[last_write] ^= 0xFF;
+    fs::write(wal_path(db_name), &content).unwrap();
+
+    drop(Database::open(db_name).unwrap());
+
+    assert!(table_files(db_name) == before);
+    assert_eq!(fs::metadata(wal_path(db_name)).unwrap().len(), 0);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_commit_completes_failed_apply_from_log() {
+    let db_name = "log_apply_failure_db";
+    setup(db_name);
+
+    let db = Database::open(db_name).unwrap();
+
+    // The record is appended to the data section, then writing its bucket node fails
+    WRITES_BEFORE_FAILURE.with(|writes| writes.set(Some(1)));
+    let oid = db.insert_record("items".to_string(), vec![1, 6]).unwrap();
+
+    assert_eq!(WRITES_BEFORE_FAILURE.with(|writes| writes.get()), None);
+    assert_eq!(db.get_record("items".to_string(), oid).unwrap(), Some(vec![1, 6]));
+    assert_eq!(fs::metadata(wal_path(db_name)).unwrap().len(), 0);
+
+    // The handle is still usable
+    let next = db.insert_record("items".to_string(), vec![1, 7]).unwrap();
+    drop(db);
+
+    let db = Database::open(db_name).unwrap();
+    assert_eq!(db.get_record("items".to_string(), oid).unwrap(), Some(vec![1, 6]));
+    assert_eq!(db.get_record("items".to_string(), next).unwrap(), Some(vec![1, 7]));
+    assert_eq!(db.count_records("items".to_string()).unwrap(), 3);
+    drop(db);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_commit_failure_that_cant_be_recovered() {
+    let db_name = "log_broken_db";
+    setup(db_name);
+    let bucket_path = Path::new(db_name).join("items_bucket.bin");
+
+    let db = Database::open(db_name).unwrap();
+    assert!(db.get_record("items".to_string(), OID { val: 1 }).unwrap().is_some());
+
+    // The open bucket can still be written, but the log can't be applied to its path
+    let bucket = fs::read(&bucket_path).unwrap();
+    fs::remove_file(&bucket_path).unwrap();
+
+    WRITES_BEFORE_FAILURE.with(|writes| writes.set(Some(1)));
+    let result = db.insert_record("items".to_string(), vec![1, 6]);
+    assert!(matches!(result, Err(Error::Io(_))));
+
+    // The handle refuses to go on, and the log keeps the transaction
+    assert!(matches!(db.get_record("items".to_string(), OID { val: 1 }), Err(Error::Poisoned(_))));
+    assert!(matches!(db.insert_record("items".to_string(), vec![1, 7]), Err(Error::Poisoned(_))));
+    assert!(fs::metadata(wal_path(db_name)).unwrap().len() > 0);
+    drop(db);
+
+    fs::write(&bucket_path, &bucket).unwrap();
+
+    let db = Database::open(db_name).unwrap();
+    assert_eq!(db.get_record("items".to_string(), OID { val: 2 }).unwrap(), Some(vec![1, 6]));
+    assert_eq!(fs::metadata(wal_path(db_name)).unwrap().len(), 0);
+    drop(db);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
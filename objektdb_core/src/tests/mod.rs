@@ -1,2 +1,9 @@
 #[cfg(test)]
-mod file_manager_tests;
\ No newline at end of file
+mod file_manager_tests;
+#[cfg(test)]
+mod database_tests;
+#[cfg(test)]
+mod from_bytes_tests;
+#[cfg(test)]
+mod to_bytes_tests;#[cfg(test)]
+mod log_manager_tests;
@@ -0,0 +1,47 @@
+use super::super::traits::{from_bytes::*, to_bytes::*};
+
+#[test]
+fn test_to_bytes_little_endian() {
+    assert_eq!((-1600i16).to_bytes(), vec![0xC0, 0xF9]);
+    assert_eq!(3_200_000u32.to_bytes(), vec![0x00, 0xD4, 0x30, 0x00]);
+    assert_eq!(1.0f32.to_bytes(), vec![0x00, 0x00, 0x80, 0x3F]);
+}
+
+#[test]
+fn test_to_bytes_bool_char_string() {
+    assert_eq!(true.to_bytes(), vec![1]);
+    assert_eq!(false.to_bytes(), vec![0]);
+    assert_eq!('ß'.to_bytes(), vec![0xDF, 0x00, 0x00, 0x00]);
+    assert_eq!("objektDB".to_string().to_bytes(), b"objektDB".to_vec());
+}
+
+#[test]
+fn test_to_bytes_pointer_sized_fixed_width() {
+    assert_eq!(1usize.to_bytes(), vec![1, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!((-1isize).to_bytes(), vec![0xFF; 8]);
+}
+
+#[test]
+fn test_from_bytes_pointer_sized_fixed_width() {
+    assert_eq!(usize::from_bytes(&[1, 0, 0, 0, 0, 0, 0, 0]), Ok(1));
+    assert_eq!(isize::from_bytes(&[0xFF; 8]), Ok(-1));
+    // Whatever the host, 4 bytes are not a usize
+    assert_eq!(usize::from_bytes(&[1, 0, 0, 0]), Err(DecodeError::Length { expected: 8, found: 4 }));
+}
+
+#[test]
+fn test_to_bytes_round_trip() {
+    fn round_trip<T: ToBytes + FromBytes + PartialEq + std::fmt::Debug>(val: T) {
+        assert_eq!(T::from_bytes(&val.to_bytes()), Ok(val));
+    }
+
+    round_trip(i8::MIN);
+    round_trip(i128::MAX);
+    round_trip(u64::MAX);
+    round_trip(u128::MAX - 1);
+    round_trip(-1.0e300f64);
+    round_trip('€');
+    round_trip(String::new());
+    round_trip(usize::MAX);
+    round_trip(isize::MIN);
+}
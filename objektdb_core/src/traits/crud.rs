@@ -1,11 +1,79 @@
-pub trait CRUD: Sized{
-    fn select() -> Vec<Self>;
+use crate::error::Error;
+use crate::storage_engine::{database::Database, file_manager::RecordIter};
+use crate::support_mods::field::OID;
+use super::objekt::Objekt;
 
-    fn save() -> Result<(), String>;
+///Operations on the records of the table of an `Objekt`.
+///
+///It is implemented by the `Objekt` derive macro: the provided methods
+///go through the `Database` handle, using the table created by `Objekt::new()`.
+pub trait CRUD: Objekt{
+    ///Reads all the objects of the table from the database.
+    ///
+    ///The whole table is loaded into memory: use `iter()` to go through
+    ///big tables one object at a time.
+    fn select(db: &Database) -> Result<Vec<Self>, Error>{
+        Self::iter(db)?.collect()
+    }
 
-    fn filter<F>(&self, condition: F) -> Vec<Self>
+    ///Scans the table in the database, reading the objects
+    ///lazily while the iterator is consumed.
+    fn iter(db: &Database) -> Result<RecordIter<Self>, Error>{
+        db.get_records::<Self>()
+    }
+
+    ///Appends the object to its table in the database and
+    ///returns the OID assigned to it.
+    fn save(&self, db: &Database) -> Result<OID, Error>{
+        db.insert_record(Self::get_table_name(), self.to_bytes()?)
+    }
+
+    ///Reads the object with the given OID from the database,
+    ///through the index of its table.
+    ///
+    ///Returns `Ok(None)` if there is no object with that OID.
+    fn get(db: &Database, oid: OID) -> Result<Option<Self>, Error>{
+        db.get_object::<Self>(oid)
+    }
+
+    ///Reads the objects of the table in the database for which
+    ///`condition` returns `true`.
+    fn filter<F>(db: &Database, condition: F) -> Result<Vec<Self>, Error>
     where
-        F: Fn(&Self) -> bool;
+        F: Fn(&Self) -> bool
+    {
+        Self::iter(db)?
+            .filter(|obj| obj.as_ref().map_or(true, &condition))
+            .collect()
+    }
+
+    ///Writes the changes made to the object to its table in the database.
+    ///
+    ///The object is found through its OID field, which doesn't change: the
+    ///record is rewritten in place, or moved if it no longer fits.
+    fn update(&self, db: &Database) -> Result<(), Error>{
+        let oid = self.get_oid()
+            .ok_or_else(|| Error::MissingOid(Self::get_table_name()))?;
+
+        if db.update_record(Self::get_table_name(), oid, self.to_bytes()?)? {
+            Ok(())
+        } else {
+            Err(Error::NotFound(format!("Object {} of {}", oid.val, Self::get_table_name())))
+        }
+    }
+
+    ///Deletes the object from its table in the database.
+    ///
+    ///The object is found through its OID field, so it must have been
+    ///read from the database or have the OID returned by `save()`.
+    fn delete(&self, db: &Database) -> Result<(), Error>{
+        let oid = self.get_oid()
+            .ok_or_else(|| Error::MissingOid(Self::get_table_name()))?;
 
-    fn delete(&self) -> Result<(), String>;
-}
\ No newline at end of file
+        if db.delete_record(Self::get_table_name(), oid)? {
+            Ok(())
+        } else {
+            Err(Error::NotFound(format!("Object {} of {}", oid.val, Self::get_table_name())))
+        }
+    }
+}
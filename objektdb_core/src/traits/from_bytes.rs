@@ -1,15 +1,55 @@
 use std::convert::TryInto;
+use std::fmt;
 
-pub trait FromBytes {
-    fn from_bytes(data: &[u8]) -> Self;
+///Decodes a value from the bytes of a field, as written by `Objekt::to_bytes()`.
+///
+///The bytes come from the files of the database, so they can be anything:
+///decoding never panics, it returns a `DecodeError` instead.
+pub trait FromBytes: Sized {
+    fn from_bytes(data: &[u8]) -> Result<Self, DecodeError>;
+}
+
+///The reason why the bytes of a field can't be decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    ///The field doesn't have the size of its type.
+    Length { expected: usize, found: usize },
+    ///The field ends after the end of the record.
+    Truncated,
+    ///A `String` field that is not valid UTF-8.
+    InvalidUtf8,
+    ///A `char` field that is not a Unicode scalar value.
+    InvalidChar(u32),
+    ///A `usize` or `isize` field that doesn't fit in the pointer width of this host.
+    OutOfRange,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Length { expected, found } => write!(f, "expected {} bytes, found {}", expected, found),
+            DecodeError::Truncated => write!(f, "the record ends before the field"),
+            DecodeError::InvalidUtf8 => write!(f, "invalid UTF-8"),
+            DecodeError::InvalidChar(val) => write!(f, "{:#x} is not a valid char", val),
+            DecodeError::OutOfRange => write!(f, "the value doesn't fit in the pointer width of this host"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+///Converts `data` to an array of `N` bytes, if it has exactly that size.
+fn fixed<const N: usize>(data: &[u8]) -> Result<[u8; N], DecodeError> {
+    data.try_into()
+        .map_err(|_| DecodeError::Length { expected: N, found: data.len() })
 }
 
 macro_rules! impl_from_bytes {
     ($($t:ty),*) => {
         $(
             impl FromBytes for $t {
-                fn from_bytes(data: &[u8]) -> Self {
-                    <$t>::from_le_bytes(data.try_into().unwrap())
+                fn from_bytes(data: &[u8]) -> Result<Self, DecodeError> {
+                    Ok(<$t>::from_le_bytes(fixed(data)?))
                 }
             }
         )*
@@ -22,54 +62,38 @@ impl_from_bytes!(i8, i16, i32, i64, i128,
 
 
 impl FromBytes for bool {
-    fn from_bytes(data: &[u8]) -> Self {
-        data[0] != 0
+    fn from_bytes(data: &[u8]) -> Result<Self, DecodeError> {
+        let [val] = fixed::<1>(data)?;
+        Ok(val != 0)
     }
 }
 
 impl FromBytes for char {
-    fn from_bytes(data: &[u8]) -> Self {
-        let val = u32::from_le_bytes(data.try_into().unwrap());
-        char::from_u32(val).unwrap()
+    fn from_bytes(data: &[u8]) -> Result<Self, DecodeError> {
+        let val = u32::from_le_bytes(fixed(data)?);
+        char::from_u32(val).ok_or(DecodeError::InvalidChar(val))
     }
 }
 
 impl FromBytes for String {
-    fn from_bytes(data: &[u8]) -> Self {
-        String::from_utf8(data.to_vec()).unwrap()
+    fn from_bytes(data: &[u8]) -> Result<Self, DecodeError> {
+        String::from_utf8(data.to_vec())
+            .map_err(|_| DecodeError::InvalidUtf8)
     }
 }
 
 
+// usize and isize are always stored in 8 bytes, whatever the pointer width of the host
 impl FromBytes for usize {
-    fn from_bytes(data: &[u8]) -> Self {
-        match std::mem::size_of::<usize>(){
-            4 => {
-                let arr: [u8; 4] = data.try_into().unwrap();
-                u32::from_le_bytes(arr) as usize
-            }
-            8 => {
-                let arr: [u8; 8] = data.try_into().unwrap();
-                u64::from_le_bytes(arr) as usize
-            }
-            _ => unreachable!(),
-        }
-        
+    fn from_bytes(data: &[u8]) -> Result<Self, DecodeError> {
+        let val = u64::from_le_bytes(fixed(data)?);
+        usize::try_from(val).map_err(|_| DecodeError::OutOfRange)
     }
 }
 
 impl FromBytes for isize {
-    fn from_bytes(data: &[u8]) -> Self {
-        match std::mem::size_of::<isize>(){
-            4 => {
-                let arr: [u8; 4] = data.try_into().unwrap();
-                i32::from_le_bytes(arr) as isize
-            }
-            8 => {
-                let arr: [u8; 8] = data.try_into().unwrap();
-                i64::from_le_bytes(arr) as isize
-            }
-            _ => unreachable!(),
-        }
+    fn from_bytes(data: &[u8]) -> Result<Self, DecodeError> {
+        let val = i64::from_le_bytes(fixed(data)?);
+        isize::try_from(val).map_err(|_| DecodeError::OutOfRange)
     }
 }
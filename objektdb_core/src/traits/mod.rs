@@ -1,4 +1,5 @@
 pub mod objekt;
 pub mod crud;
 pub mod from_bytes;
+pub mod to_bytes;
 pub mod impl_block;
\ No newline at end of file
@@ -1,11 +1,32 @@
+use crate::error::Error;
+use crate::storage_engine::database::Database;
+use crate::support_mods::field::{Field, OID};
+
 pub trait Objekt: Sized{
 
+    fn get_table_name() -> String;
     fn get_field_types() -> Vec<String>;
-    fn record_from_bytes(data: Vec<u8>)-> Option<Self>;
-    fn to_bytes(&self)-> Vec<u8>;
+
+    //the schema of the table: name, type and flags of each field, in the order of the records
+    fn get_fields() -> Vec<Field>;
+
+    //decodes a record written by to_bytes(), Error::CorruptField if a field is malformed
+    fn record_from_bytes(data: Vec<u8>)-> Result<Self, Error>;
+    //encodes the object as a record, Error::ValueTooLong if a field value is longer than 255 bytes
+    fn to_bytes(&self)-> Result<Vec<u8>, Error>;
+
+    //the OID field of the struct, if any
+    fn get_oid(&self) -> Option<OID>;
+
+    //assigns the OID of the record to the OID field of the struct, if any
+    fn set_oid(&mut self, oid: OID);
     
-    //for creating the table(using file_manager::crate_table())
-    fn new(struct_name: String)-> Result<(), String>;
+    //binds the Ref fields to the database the object was read from, so that Ref::load() can read their target
+    fn bind(&mut self, _db: &Database){}
+
+    //for creating the table, or checking the schema of the existing one (using Database::ensure_table())
+    #[allow(clippy::new_ret_no_self)]
+    fn new(db: &Database)-> Result<(), Error>;
 
 
-}
\ No newline at end of file
+}
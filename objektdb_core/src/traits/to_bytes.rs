@@ -0,0 +1,57 @@
+///Encodes a value as the bytes of a field, the inverse of `FromBytes`.
+///
+///Numbers are written in little-endian order, `bool` as 1 byte, `char` as its
+///`u32` value and `String` as its UTF-8 bytes. `usize` and `isize` always take
+///8 bytes, so a file written on a 32-bit host can be read on a 64-bit one and
+///vice versa.
+pub trait ToBytes {
+    fn to_bytes(&self) -> Vec<u8>;
+}
+
+macro_rules! impl_to_bytes {
+    ($($t:ty),*) => {
+        $(
+            impl ToBytes for $t {
+                fn to_bytes(&self) -> Vec<u8> {
+                    self.to_le_bytes().to_vec()
+                }
+            }
+        )*
+    };
+}
+
+impl_to_bytes!(i8, i16, i32, i64, i128,
+               u8, u16, u32, u64, u128,
+               f32, f64);
+
+
+impl ToBytes for bool {
+    fn to_bytes(&self) -> Vec<u8> {
+        vec![*self as u8]
+    }
+}
+
+impl ToBytes for char {
+    fn to_bytes(&self) -> Vec<u8> {
+        (*self as u32).to_le_bytes().to_vec()
+    }
+}
+
+impl ToBytes for String {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+
+impl ToBytes for usize {
+    fn to_bytes(&self) -> Vec<u8> {
+        (*self as u64).to_le_bytes().to_vec()
+    }
+}
+
+impl ToBytes for isize {
+    fn to_bytes(&self) -> Vec<u8> {
+        (*self as i64).to_le_bytes().to_vec()
+    }
+}
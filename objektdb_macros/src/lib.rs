@@ -1,20 +1,17 @@
 use proc_macro::TokenStream;
-use quote::{quote, ToTokens, format_ident};
+use quote::{quote, ToTokens};
 use syn::{
     parse_macro_input,
-    parse_str,
     Data,
     DeriveInput,
     Fields,
     GenericArgument,
-    Ident,
     ItemStruct,
     LitStr,
     PathArguments,
     Type,
     ItemImpl,
-    ImplItem,
-    TypePath
+    ImplItem
 };
 use proc_macro2::{self};
 use proc_macro2::Span;
@@ -56,29 +53,47 @@ pub fn objekt_impl(args: TokenStream, input: TokenStream) -> TokenStream {
 
 }
 
-///It should be inserted on top of your structs to declare that it is an entity whose 
-/// instances you want to store. The database in which you want to store the data should 
-/// be specified.
-///If the specified database does not exist, it is automatically created: so if you mistype 
-/// the database name and enter one that does not exist, a new one will automatically be 
-/// created. For the moment , since a language for interacting with data directly is not yet 
-/// available, to delete a database you can make use of the function `delete_db(“database_name”)`.
-/// 
+///It should be derived for your structs to declare that they are entities whose
+/// instances you want to store. Each struct is stored in a table named after it, in the
+/// database passed to its functions (or the one of the `#[odb]` struct that has a `Set` of it).
+///The fields are `Primitive<T>`, `Ref<T>` or `RefSet<T>`, plus at most one `OID` field
+/// that gets the OID assigned by `save()`. A field can be marked with `#[objekt(default)]` and
+/// `#[objekt(on_delete = "cascade" | "restrict" | "set_null")]`.
+///
 /// In addition, it also implements CRUD trait to perform transactions on the database.
 /// In particular, it implements the following functions:
+/// - `save()`
+/// - `get()`
 /// - `select()`
+/// - `iter()`
+/// - `filter()`
 /// - `update()`
-/// - `where()`
 /// - `delete()`
+///
+/// `new(&db)` creates the table of the struct, or checks that the existing one was
+/// created with the same fields: if the struct has changed it returns
+/// `Error::SchemaMismatch`, and the records can be converted with `db.migrate()`.
 /// # Example
 /// ```ignore
-/// use objektDB::*;
-/// #[objekt("my_database.db")]
+/// use objektdb::*;
+///
+/// #[derive(Objekt)]
 /// struct Person {
-///    name: String,
-///    age: u32,
+///     id: OID,
+///     name: Primitive<String>,
+///     age: Primitive<u32>,
 /// }
-#[proc_macro_derive(Objekt)] //Need to change to derive macro(Change in architecture)
+///
+/// let db = Database::open_or_create("my_database")?;
+/// Person::new(&db)?;
+///
+/// let oid = Person {
+///     id: OID { val: 0 },
+///     name: Primitive { val: "Ada".to_string() },
+///     age: Primitive { val: 36 },
+/// }.save(&db)?;
+/// ```
+#[proc_macro_derive(Objekt, attributes(objekt))] //Need to change to derive macro(Change in architecture)
 pub fn objekt_derive(input: TokenStream) -> TokenStream {
 
     let item = parse_macro_input!(input as DeriveInput);
@@ -159,8 +174,17 @@ pub fn objekt_derive(input: TokenStream) -> TokenStream {
                                 panic!("Primitive must be parameterized with a generic type");
                             }
                         }
+                        // A reference is stored as the OID of its target, a RefSet is not stored in the record
+                        "Ref" | "RefSet" => {
+                            if !matches!(&last_segment.arguments, syn::PathArguments::AngleBracketed(args)
+                                if matches!(args.args.first(), Some(GenericArgument::Type(_))))
+                            {
+                                panic!("{} must be parameterized with the type of the referenced struct", type_name);
+                            }
+                            syn::parse_quote!(i32)
+                        }
                         _ => {
-                            panic!("Unsupported type: '{}'. Only OID and Primitive<T> are supported", type_name);
+                            panic!("Unsupported type: '{}'. Only OID, Primitive<T>, Ref<T> and RefSet<T> are supported", type_name);
                         }
                     }
                 } else {
@@ -168,38 +192,95 @@ pub fn objekt_derive(input: TokenStream) -> TokenStream {
                 }
             }
             _ => {
-                panic!("Unsupported type. Only path types (OID and Primitive<T>) are supported");
+                panic!("Unsupported type. Only path types (OID, Primitive<T>, Ref<T> and RefSet<T>) are supported");
             }
         }
     }).collect();
 
-    let fields_names_literals: Vec<_> = fields_inner_types.iter().map(|n|{
-        LitStr::new(n.to_token_stream().to_string().as_ref(), Span::call_site())
-    }).collect();
+    //the #[objekt(default)] and #[objekt(on_delete)] of each field
+    let fields_attributes: Vec<FieldAttributes> = if let Data::Struct(data) = &item.data {
+        data.fields.iter().map(field_attributes).collect()
+    } else {
+        panic!("Only structs are supported");
+    };
 
    let mut field_definitions = Vec::new();
     let mut field_constructions = Vec::new();
+    let mut field_encodings = Vec::new();
+    let mut oid_field = None;
+    //the Ref fields and the types they point at
+    let mut ref_fields = Vec::new();
+    let mut ref_targets: Vec<Type> = Vec::new();
+    //the RefSet fields, their names and the types they point at
+    let mut ref_set_fields = Vec::new();
+    let mut ref_set_names = Vec::new();
+    let mut ref_set_targets: Vec<Type> = Vec::new();
+
+
+    for (((t, n), inner_ty), attributes) in fields_types.iter().zip(fields_names.iter()).zip(fields_inner_types).zip(fields_attributes) {
+        let field_name_lit = LitStr::new(&n.to_string(), Span::call_site());
+        let FieldAttributes { default, on_delete } = attributes;
+        let has_default = default.is_some();
+
+        let is_ref = matches!(t, Type::Path(type_path) if type_path.path.segments.last().is_some_and(|segment| segment.ident == "Ref"));
+        if on_delete.is_some() && !is_ref {
+            panic!("on_delete can only be used on a Ref field, {} is not", n);
+        }
+        let on_delete = on_delete.unwrap_or(quote! { Restrict });
+
+        // A RefSet is in its join table: it has no field in the schema and no value in the record
+        if let Type::Path(type_path) = t
+            && let Some(last_segment) = type_path.path.segments.last()
+            && last_segment.ident == "RefSet"
+            && let syn::PathArguments::AngleBracketed(args) = &last_segment.arguments
+            && let Some(GenericArgument::Type(target)) = args.args.first()
+        {
+            if has_default {
+                panic!("A RefSet can't have a default value, it starts empty");
+            }
 
+            field_constructions.push(quote! {
+                let #n = objektdb::objektdb_core::support_mods::reference::RefSet::<#target>::default();
+            });
+            ref_set_fields.push(n.clone());
+            ref_set_names.push(field_name_lit);
+            ref_set_targets.push(target.clone());
+            continue;
+        }
+
+        // The value written in the record, and the field built from #default if it's missing
+        let mut value_access = quote! { self.#n.val };
+        let mut missing_value = default.as_ref().map(|default| quote! {
+            objektdb::objektdb_core::support_mods::field::Primitive::<#inner_ty> { val: #default }
+        });
 
-    for ((t, n), inner_ty) in fields_types.iter().zip(fields_names.iter()).zip(fields_inner_types) {
         let constructor = match t {
             Type::Path(type_path) => {
                 if let Some(last_segment) = type_path.path.segments.last() {
                     match last_segment.ident.to_string().as_str() {
                         "OID" => {
-                            let inner_ty_lit = LitStr::new(inner_ty.to_token_stream().to_string().as_ref(), Span::call_site());
-                            
+                            if oid_field.replace(n.clone()).is_some() {
+                                panic!("Only one OID field is allowed, it contains the object id assigned by save()");
+                            }
+                            if has_default {
+                                panic!("The OID field can't have a default value, it's assigned by save()");
+                            }
 
                             field_definitions.push(quote!{
                                 objektdb::objektdb_core::support_mods::field::Field{
-                                    name: #inner_ty_lit.to_string(),
-                                    is_oid: true
+                                    name: #field_name_lit.to_string(),
+                                    is_oid: true,
+                                    is_fk: false,
+                                    has_default: false,
+                                    on_delete: objektdb::objektdb_core::support_mods::field::OnDelete::Restrict,
+                                    type_: "OID".to_string()
                                 }
                             });
 
                             quote! {
                                 objektdb::objektdb_core::support_mods::field::OID {
-                                    val: #inner_ty::from_bytes(&data[next_start..end])
+                                    val: <#inner_ty as objektdb::objektdb_core::traits::from_bytes::FromBytes>::from_bytes(&data[next_start..end])
+                                        .map_err(|error| corrupt(#field_name_lit, next_start, error))?
                                 }
                             }
                         }
@@ -208,15 +289,58 @@ pub fn objekt_derive(input: TokenStream) -> TokenStream {
                             
                             field_definitions.push(quote!{
                                 objektdb::objektdb_core::support_mods::field::Field{
-                                    name: #inner_ty_lit.to_string(),
-                                    is_oid: false
+                                    name: #field_name_lit.to_string(),
+                                    is_oid: false,
+                                    is_fk: false,
+                                    has_default: #has_default,
+                                    on_delete: objektdb::objektdb_core::support_mods::field::OnDelete::Restrict,
+                                    type_: #inner_ty_lit.to_string()
                                 }
                             });
 
                             quote! {
                                 objektdb::objektdb_core::support_mods::field::Primitive::<#inner_ty> {
-                                    val: #inner_ty::from_bytes(&data[next_start..end])
+                                    val: <#inner_ty as objektdb::objektdb_core::traits::from_bytes::FromBytes>::from_bytes(&data[next_start..end])
+                                        .map_err(|error| corrupt(#field_name_lit, next_start, error))?
+                                }
+                            }
+                        }
+                        "Ref" => {
+                            let Some(GenericArgument::Type(target)) = (match &last_segment.arguments {
+                                syn::PathArguments::AngleBracketed(args) => args.args.first(),
+                                _ => None,
+                            }) else {
+                                unreachable!() // checked with the inner types
+                            };
+
+                            // The table of the target, whatever path is used for its type
+                            field_definitions.push(quote!{
+                                objektdb::objektdb_core::support_mods::field::Field{
+                                    name: #field_name_lit.to_string(),
+                                    is_oid: false,
+                                    is_fk: true,
+                                    has_default: #has_default,
+                                    on_delete: objektdb::objektdb_core::support_mods::field::OnDelete::#on_delete,
+                                    type_: format!("Ref<{}>", <#target as objektdb::objektdb_core::traits::objekt::Objekt>::get_table_name())
                                 }
+                            });
+
+                            value_access = quote! { self.#n.oid.val };
+                            missing_value = default.as_ref().map(|default| quote! {
+                                objektdb::objektdb_core::support_mods::reference::Ref::<#target>::new(
+                                    objektdb::objektdb_core::support_mods::field::OID { val: #default }
+                                )
+                            });
+                            ref_fields.push(n.clone());
+                            ref_targets.push(target.clone());
+
+                            quote! {
+                                objektdb::objektdb_core::support_mods::reference::Ref::<#target>::new(
+                                    objektdb::objektdb_core::support_mods::field::OID {
+                                        val: <#inner_ty as objektdb::objektdb_core::traits::from_bytes::FromBytes>::from_bytes(&data[next_start..end])
+                                            .map_err(|error| corrupt(#field_name_lit, next_start, error))?
+                                    }
+                                )
                             }
                         }
                         _ => panic!("Unsupported type")
@@ -228,19 +352,63 @@ pub fn objekt_derive(input: TokenStream) -> TokenStream {
             _ => panic!("Unsupported type")
         };
 
+        // Same layout read back by record_from_bytes: 1 byte of length, then the value (see ToBytes)
+        let value_bytes = quote! {
+            <#inner_ty as objektdb::objektdb_core::traits::to_bytes::ToBytes>::to_bytes(&#value_access)
+        };
+
+        field_encodings.push(quote! {
+            let value: Vec<u8> = #value_bytes;
+            bytes.push(
+                u8::try_from(value.len()).map_err(|_| objektdb::objektdb_core::error::Error::ValueTooLong {
+                    table: <Self as objektdb::objektdb_core::traits::objekt::Objekt>::get_table_name(),
+                    field: #field_name_lit.to_string(),
+                    len: value.len(),
+                })?
+            );
+            bytes.extend_from_slice(&value);
+        });
+
+        // A record written before the field was added ends before it
+        let missing = match missing_value {
+            Some(value) => value,
+            None => quote! {
+                return Err(corrupt(#field_name_lit, start, objektdb::objektdb_core::traits::from_bytes::DecodeError::Truncated))
+            },
+        };
+
         // Crea il blocco di costruzione del field
         field_constructions.push(quote! {
-            if start >= data.len() { return None; }
-            let dim = data[start] as usize;
-            let next_start = start + 1;
-            let end = next_start + dim;
-            if end > data.len() { return None; }
-            let #n = #constructor;
-            start = end;
+            let #n = if start >= data.len() {
+                #missing
+            } else {
+                let dim = data[start] as usize;
+                let next_start = start + 1;
+                let end = next_start + dim;
+                if end > data.len() {
+                    return Err(corrupt(#field_name_lit, start, objektdb::objektdb_core::traits::from_bytes::DecodeError::Truncated));
+                }
+                let value = #constructor;
+                start = end;
+                value
+            };
         });
     }
 
-    let mut methods_n;
+    let ref_set_binds = match &oid_field {
+        Some(field) => quote! {
+            #(self.#ref_set_fields.bind(db, #name_lit_str, #ref_set_names, self.#field);)*
+        },
+        None if ref_set_fields.is_empty() => quote! {},
+        None => panic!("A struct with a RefSet must have an OID field, the join table refers to the objects through it"),
+    };
+
+    let (oid_value, oid_assignment) = match &oid_field {
+        Some(field) => (quote! { Some(self.#field) }, quote! { self.#field = oid; }),
+        None => (quote! { None }, quote! { let _ = oid; }),
+    };
+
+    let methods_n;
     #[cfg(feature="impl_blocks")]{
         methods_n = quote! {
             let methods_names = Self::get_methods_names();
@@ -256,15 +424,32 @@ pub fn objekt_derive(input: TokenStream) -> TokenStream {
 
     let expanded = quote! {
         impl objektdb::objektdb_core::traits::objekt::Objekt for #name{
+            fn get_table_name() -> String{
+                #name_lit_str.to_string()
+            }
+
             fn get_field_types() -> Vec<String>{
                 vec![#(#field_type_literals.to_string()),*]
             }
 
-            fn record_from_bytes(data: Vec<u8>)->Option<Self>{
+            fn get_fields() -> Vec<objektdb::objektdb_core::support_mods::field::Field>{
+                vec![
+                    #(#field_definitions),*
+                ]
+            }
+
+            #[allow(unused_variables, unused_mut)]
+            fn record_from_bytes(data: Vec<u8>)->Result<Self, objektdb::objektdb_core::Error>{
 
-                if data.is_empty(){
-                    return None;
-                }
+                // The offset is relative to the record, the storage engine makes it absolute
+                let corrupt = |field: &str, offset: usize, error: objektdb::objektdb_core::traits::from_bytes::DecodeError| {
+                    objektdb::objektdb_core::Error::CorruptField {
+                        table: Self::get_table_name(),
+                        field: field.to_string(),
+                        offset: offset as u64,
+                        error,
+                    }
+                };
 
                 let mut start: usize = 0;
                 
@@ -272,32 +457,69 @@ pub fn objekt_derive(input: TokenStream) -> TokenStream {
                     #field_constructions
                 )*
                
-               Some(Self{
+               Ok(Self{
                 #(#fields_names: #fields_names),*
                })
                     
             }
 
-            fn to_bytes(&self)-> Vec<u8>{
-                todo!()
+            fn get_oid(&self) -> Option<objektdb::objektdb_core::support_mods::field::OID>{
+                #oid_value
+            }
+
+            fn set_oid(&mut self, oid: objektdb::objektdb_core::support_mods::field::OID){
+                #oid_assignment
             }
 
-            fn new(struct_name: String)-> Result<(), String>{
+            fn to_bytes(&self)-> Result<Vec<u8>, objektdb::objektdb_core::error::Error>{
+                let mut bytes: Vec<u8> = Vec::new();
+
+                #(
+                    #field_encodings
+                )*
+
+                Ok(bytes)
+            }
+
+            #[allow(unused_variables)]
+            fn bind(&mut self, db: &objektdb::objektdb_core::storage_engine::database::Database){
+                #(self.#ref_fields.bind(db);)*
+                #ref_set_binds
+            }
+
+            fn new(db: &objektdb::objektdb_core::storage_engine::database::Database)-> Result<(), objektdb::objektdb_core::Error>{
                 
                #methods_n
-                let mut fields_obj: Vec<objektdb::objektdb_core::support_mods::field::Field> = vec![
-                    #(#field_definitions),*
-                ];
 
-                objektdb::objektdb_core::storage_engine::file_manager::create_table(
+                // The tables of the Ref fields, each one once
+                #[allow(unused_mut)]
+                let mut references: Vec<String> = Vec::new();
+                #(
+                    let table = <#ref_targets as objektdb::objektdb_core::traits::objekt::Objekt>::get_table_name();
+                    if !references.contains(&table) {
+                        references.push(table);
+                    }
+                )*
+
+                db.ensure_table(
                     #name_lit_str.to_string(), 
-                    struct_name, 
-                    fields_obj, 
+                    references,
+                    Self::get_fields(), 
                     methods_names
-                )
+                )?;
+
+                #(
+                    objektdb::objektdb_core::support_mods::reference::RefSet::<#ref_set_targets>::ensure_join_table(
+                        db, #name_lit_str, #ref_set_names
+                    )?;
+                )*
+
+                Ok(())
             }
         }
 
+        impl objektdb::objektdb_core::traits::crud::CRUD for #name{}
+
 
     };
 
@@ -305,70 +527,130 @@ pub fn objekt_derive(input: TokenStream) -> TokenStream {
 
 }
 
+///It should be inserted on top of the struct that describes a database, whose fields are
+/// the `Set<T>` of the structs stored in it. The name of the database is the argument of the macro.
+///
+/// `new()` opens the database in the current working directory (`open(path)` anywhere else),
+/// creating it if it doesn't exist, and calls `T::new()` for each `Set<T>`, which creates the
+/// table of `T` or checks its schema. The struct is returned with each `Set` bound to its table,
+/// or the first error.
+/// # Example
+/// ```ignore
+/// #[odb("library")]
+/// struct Library {
+///     books: Set<Book>,
+///     authors: Set<Author>,
+/// }
+///
+/// let library = Library::new()?;
+/// ```
 #[proc_macro_attribute]
 pub fn odb(attr: TokenStream, item: TokenStream) -> TokenStream {
     let input = parse_macro_input!(item as ItemStruct);
     let struct_name = &input.ident;
     let db_name_lit: LitStr = parse_macro_input!(attr as LitStr);
 
-    // fields will be Set<T> type. Here we'll put T
-    let mut set_types: Vec<Type> = Vec::new();
-    let mut set_types_literal: Vec<LitStr> = Vec::new();
-    let mut params: Vec<proc_macro2::TokenStream> = Vec::new();
-
+    let mut sets: Vec<proc_macro2::TokenStream> = Vec::new();
 
-    //Extract T from Set<T>
+    //Each field must be a Set<T>
     match &input.fields {
         syn::Fields::Named(field) => {
             for f in field.named.iter() {
                 let f_name = &f.ident;
                 let f_type = &f.ty;
-                params.push(quote! { #f_name: #f_type });
-
-                if let Type::Path(ty_path) = &f_type {
-                    if let Some(segment) = ty_path.path.segments.last() {
-                        if let PathArguments::AngleBracketed(ref generics) = segment.arguments {
-                            if let Some(GenericArgument::Type(inner_ty)) = generics.args.first() {
-                                let inner_ty_str = quote!(#inner_ty).to_string();
-                                if !set_types.iter().any(|ty| quote!(#ty).to_string() == inner_ty_str) {
-                                    set_types.push(inner_ty.clone());
-                                }
-                            }
-                        }
-                    }
+
+                let is_set = if let Type::Path(ty_path) = &f_type
+                    && let Some(segment) = ty_path.path.segments.last()
+                    && let PathArguments::AngleBracketed(ref generics) = segment.arguments
+                    && let Some(GenericArgument::Type(_)) = generics.args.first()
+                {
+                    segment.ident == "Set"
+                } else {
+                    false
+                };
+
+                if !is_set {
+                    panic!("The fields of an #[odb] struct must be Set<T>, {} is not", quote!(#f_name));
                 }
+
+                sets.push(quote! { #f_name: <#f_type>::open(&db)? });
             }
         }
         _ => panic!("The #[odb] macro can only be used with structures with named fields"),
     }
 
-    set_types_literal = set_types
-        .iter()
-        .map(|t| {
-            LitStr::new(t.to_token_stream().to_string().as_ref(), Span::call_site())
-        })
-        .collect();
-
-
-
-
-
     TokenStream::from(quote! {
         #input
 
         impl #struct_name {
-            /// Create db and tables
-            pub fn new() -> Result<(), String> {
-                objektdb::objektdb_core::storage_engine::file_manager::create_db(#db_name_lit.to_string());
+            /// Opens the database in the current working directory, creating it if it doesn't exist,
+            /// and binds each `Set` to its table
+            pub fn new() -> Result<Self, objektdb::objektdb_core::Error> {
+                let current_dir = std::env::current_dir()?;
 
-                let mut set_types: Vec<String> = Vec::new();
+                Self::open(current_dir.join(#db_name_lit))
+            }
 
+            /// Opens the database in the directory `path`, creating it if it doesn't exist,
+            /// and binds each `Set` to its table
+            pub fn open<P: AsRef<std::path::Path>>(path: P) -> Result<Self, objektdb::objektdb_core::Error> {
+                let db = objektdb::objektdb_core::storage_engine::database::Database::open_or_create(path)?;
 
-                Ok(())
+                Ok(#struct_name {
+                    #(#sets,)*
+                })
             }
         }
     })
 }
 
+///The `#[objekt(...)]` attributes of a field.
+#[derive(Default)]
+struct FieldAttributes {
+    ///The value of `default`
+    default: Option<proc_macro2::TokenStream>,
+    ///The `OnDelete` variant of `on_delete`
+    on_delete: Option<proc_macro2::TokenStream>,
+}
+
+///Reads `#[objekt(default = value)]` or `#[objekt(default)]`, and
+///`#[objekt(on_delete = "cascade" | "restrict" | "set_null")]` on a field.
+///
+///A string literal is converted to a `String`, `#[objekt(default)]` uses `Default::default()`.
+fn field_attributes(field: &syn::Field) -> FieldAttributes {
+    let mut attributes = FieldAttributes::default();
+
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("objekt")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("on_delete") {
+                let policy: LitStr = meta.value()?.parse()?;
+
+                attributes.on_delete = Some(match policy.value().as_str() {
+                    "cascade" => quote! { Cascade },
+                    "restrict" => quote! { Restrict },
+                    "set_null" => quote! { SetNull },
+                    _ => return Err(meta.error("Unsupported on_delete, expected \"cascade\", \"restrict\" or \"set_null\"")),
+                });
+
+                return Ok(());
+            }
+
+            if !meta.path.is_ident("default") {
+                return Err(meta.error("Unsupported objekt attribute, expected `default`, `default = value` or `on_delete = \"...\"`"));
+            }
+
+            attributes.default = Some(if meta.input.peek(syn::Token![=]) {
+                match meta.value()?.parse::<syn::Expr>()? {
+                    syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(lit), .. }) => quote! { #lit.to_string() },
+                    expr => quote! { #expr },
+                }
+            } else {
+                quote! { ::core::default::Default::default() }
+            });
 
+            Ok(())
+        }).unwrap_or_else(|e| panic!("{}", e));
+    }
 
+    attributes
+}
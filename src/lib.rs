@@ -4,22 +4,28 @@ pub use objektdb_macros;
 
 pub use objektdb_macros::{Objekt, objekt_impl, odb};
 pub use objektdb_core::{
+    Error,
     storage_engine::{
+        database::{Database, CatalogCheck},
+        raw_record::RawRecord,
         file_manager::{
             create_db, 
             create_table, 
+            RecordIter,
+            TableEntry,
             delete_db, 
             reinitialize_table
         }
     }, 
     support_mods::{
-        support_functions::*,
         field::*,
-        set::*
+        set::*,
+        reference::{Ref, RefSet}
     },
     traits::{
         crud,
-        from_bytes::FromBytes,
+        from_bytes::{DecodeError, FromBytes},
+        to_bytes::ToBytes,
         impl_block::ImplBlock
     }
 };
\ No newline at end of file
@@ -0,0 +1,227 @@
+use objektdb::{crud::CRUD, objektdb_core::traits::objekt::Objekt, Database, Error, Objekt, OID, Primitive};
+use std::fs;
+
+#[derive(Objekt, Debug, PartialEq)]
+struct Person {
+    id: OID,
+    name: Primitive<String>,
+    age: Primitive<u8>,
+}
+
+fn person(name: &str, age: u8) -> Person {
+    Person {
+        id: OID { val: 0 },
+        name: Primitive { val: name.to_string() },
+        age: Primitive { val: age },
+    }
+}
+
+fn setup(db_name: &str) -> Database {
+    let _ = fs::remove_dir_all(db_name);
+    let db = Database::create(db_name).unwrap();
+    Person::new(&db).unwrap();
+    db
+}
+
+#[test]
+fn test_save_assigns_sequential_oids() {
+    let db_name = "crud_save_db";
+    let db = setup(db_name);
+
+    let first = person("Ada", 36).save(&db).unwrap();
+    let second = person("Linus", 54).save(&db).unwrap();
+
+    assert_eq!(first, OID { val: 1 });
+    assert_eq!(second, OID { val: 2 });
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_save_without_table() {
+    let db_name = "crud_save_no_table_db";
+    let _ = fs::remove_dir_all(db_name);
+    let db = Database::create(db_name).unwrap();
+
+    assert!(person("Ada", 36).save(&db).is_err());
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_save_value_too_long() {
+    let db_name = "crud_save_too_long_db";
+    let db = setup(db_name);
+
+    let long = person(&"x".repeat(300), 36);
+
+    let result = long.save(&db);
+    assert!(matches!(
+        result,
+        Err(Error::ValueTooLong { ref table, ref field, len: 300 }) if table == "Person" && field == "name"
+    ));
+    assert_eq!(db.count_records("Person".to_string()).unwrap(), 0);
+
+    // Nor can an object be updated with one
+    let mut ada = Person::get(&db, person("Ada", 36).save(&db).unwrap()).unwrap().unwrap();
+    ada.name.val = "y".repeat(256);
+    assert!(matches!(ada.update(&db), Err(Error::ValueTooLong { len: 256, .. })));
+    assert_eq!(Person::get(&db, ada.id).unwrap().unwrap().name.val, "Ada");
+
+    // 255 bytes are fine
+    assert!(person(&"z".repeat(255), 1).save(&db).is_ok());
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_get_by_oid() {
+    let db_name = "crud_get_db";
+    let db = setup(db_name);
+
+    let ada = person("Ada", 36).save(&db).unwrap();
+    let linus = person("Linus", 54).save(&db).unwrap();
+
+    let found = Person::get(&db, linus).unwrap().unwrap();
+    assert_eq!(found.id, linus);
+    assert_eq!(found.name.val, "Linus");
+    assert_eq!(found.age.val, 54);
+
+    let found = Person::get(&db, ada).unwrap().unwrap();
+    assert_eq!(found, Person { id: ada, ..person("Ada", 36) });
+
+    assert_eq!(Person::get(&db, OID { val: 3 }).unwrap(), None);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_select_and_filter() {
+    let db_name = "crud_select_db";
+    let db = setup(db_name);
+
+    assert!(Person::select(&db).unwrap().is_empty());
+
+    person("Ada", 36).save(&db).unwrap();
+    person("Linus", 54).save(&db).unwrap();
+    person("Grace", 85).save(&db).unwrap();
+
+    let all = Person::select(&db).unwrap();
+    let names: Vec<_> = all.iter().map(|p| p.name.val.as_str()).collect();
+    assert_eq!(names, ["Ada", "Linus", "Grace"]);
+    assert_eq!(all[2].id, OID { val: 3 });
+
+    let older = Person::filter(&db, |p| p.age.val > 50).unwrap();
+    assert_eq!(older.len(), 2);
+    assert_eq!(older[0].name.val, "Linus");
+
+    let first = Person::iter(&db).unwrap().next().unwrap().unwrap();
+    assert_eq!(first, Person { id: OID { val: 1 }, ..person("Ada", 36) });
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_delete() {
+    let db_name = "crud_delete_db";
+    let db = setup(db_name);
+
+    person("Ada", 36).save(&db).unwrap();
+    let linus = person("Linus", 54).save(&db).unwrap();
+
+    let found = Person::get(&db, linus).unwrap().unwrap();
+    found.delete(&db).unwrap();
+
+    assert_eq!(Person::get(&db, linus).unwrap(), None);
+    let names: Vec<_> = Person::select(&db).unwrap().into_iter().map(|p| p.name.val).collect();
+    assert_eq!(names, ["Ada"]);
+
+    assert!(found.delete(&db).is_err());
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_update_keeps_oid() {
+    let db_name = "crud_update_db";
+    let db = setup(db_name);
+
+    let ada = person("Ada", 36).save(&db).unwrap();
+    person("Linus", 54).save(&db).unwrap();
+
+    let mut found = Person::get(&db, ada).unwrap().unwrap();
+
+    // Fits in the old record
+    found.age.val = 37;
+    found.update(&db).unwrap();
+    assert_eq!(Person::get(&db, ada).unwrap().unwrap().age.val, 37);
+
+    // Doesn't fit anymore
+    found.name.val = "Ada Lovelace".to_string();
+    found.update(&db).unwrap();
+
+    let updated = Person::get(&db, ada).unwrap().unwrap();
+    assert_eq!(updated, Person { id: ada, ..person("Ada Lovelace", 37) });
+    assert_eq!(Person::select(&db).unwrap().len(), 2);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_update_without_oid() {
+    let db_name = "crud_update_no_oid_db";
+    let db = setup(db_name);
+
+    // Never saved: there is no record with OID 0
+    assert!(person("Ada", 36).update(&db).is_err());
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_get_reports_corrupt_field_position() {
+    let db_name = "crud_get_corrupt_db";
+    let db = setup(db_name);
+
+    let oid = person("Ada", 36).save(&db).unwrap();
+
+    // The first record starts at the beginning of the data section: header,
+    // then the OID field (1 + 4 bytes), then the length of the name
+    let tbl_path = std::path::Path::new(db_name).join("Person.tbl");
+    let mut tbl = fs::read(&tbl_path).unwrap();
+    let offset_header = u32::from_le_bytes(tbl[64..68].try_into().unwrap()) as usize;
+    let name_start = offset_header + objektdb::objektdb_core::storage_engine::file_manager::INDEX_SIZE + 12 + 5 + 1;
+    tbl[name_start] = 0xFF;
+    fs::write(&tbl_path, &tbl).unwrap();
+
+    match Person::get(&db, oid) {
+        Err(Error::CorruptField { table, field, offset, .. }) => {
+            assert_eq!(table, "Person");
+            assert_eq!(field, "name");
+            assert_eq!(offset, name_start as u64);
+        },
+        other => panic!("expected a corrupt field, got {:?}", other),
+    }
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_new_writes_schema() {
+    let db_name = "crud_schema_db";
+    let db = setup(db_name);
+
+    let schema = db.read_schema("Person").unwrap();
+    let fields: Vec<_> = schema.fields.iter()
+        .map(|field| (field.name.as_str(), field.type_.as_str(), field.is_oid, field.is_fk))
+        .collect();
+
+    assert_eq!(schema.name, "Person");
+    assert_eq!(fields, vec![
+        ("id", "OID", true, false),
+        ("name", "String", false, false),
+        ("age", "u8", false, false),
+    ]);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
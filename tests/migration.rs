@@ -0,0 +1,378 @@
+use objektdb::{
+    crud::CRUD, objektdb_core::{storage_engine::raw_record::RawRecord, traits::objekt::Objekt},
+    Database, Error, OID, Primitive,
+};
+use std::fs;
+
+// Two versions of the same struct, stored in the table Person
+mod v1 {
+    use objektdb::{Objekt, OID, Primitive};
+
+    #[derive(Objekt, Debug, PartialEq)]
+    pub struct Person {
+        pub id: OID,
+        pub name: Primitive<String>,
+        pub age: Primitive<u8>,
+        pub nickname: Primitive<String>,
+    }
+}
+
+mod v2 {
+    use objektdb::{Objekt, OID, Primitive};
+
+    #[derive(Objekt, Debug, PartialEq)]
+    pub struct Person {
+        pub id: OID,
+        pub name: Primitive<String>,
+        pub age: Primitive<u16>,
+        pub email: Primitive<String>,
+    }
+}
+
+// A third version that refers to a company, which needs a migration
+mod v3 {
+    use objektdb::{Objekt, Ref, OID, Primitive};
+
+    #[derive(Objekt, Debug, PartialEq)]
+    pub struct Company {
+        pub id: OID,
+        pub name: Primitive<String>,
+    }
+
+    #[derive(Objekt, Debug, PartialEq)]
+    pub struct Person {
+        pub id: OID,
+        pub name: Primitive<String>,
+        pub employer: Ref<Company>,
+    }
+}
+
+fn setup(db_name: &str) -> (Database, Vec<OID>) {
+    let _ = fs::remove_dir_all(db_name);
+    let db = Database::create(db_name).unwrap();
+    v1::Person::new(&db).unwrap();
+
+    let oids = [("Ada", 36), ("Linus", 54), ("Grace", 85)].iter()
+        .map(|(name, age)| v1::Person {
+            id: OID { val: 0 },
+            name: Primitive { val: name.to_string() },
+            age: Primitive { val: *age },
+            nickname: Primitive { val: String::new() },
+        }.save(&db).unwrap())
+        .collect();
+
+    (db, oids)
+}
+
+fn to_v2(old: RawRecord) -> Result<v2::Person, Error> {
+    let name: String = old.get("name")?;
+
+    Ok(v2::Person {
+        id: old.oid(),
+        email: Primitive { val: format!("{}@example.com", name.to_lowercase()) },
+        name: Primitive { val: name },
+        age: Primitive { val: old.get::<u8>("age")? as u16 },
+    })
+}
+
+#[test]
+fn test_new_accepts_same_schema() {
+    let db_name = "migration_same_db";
+    let (db, _) = setup(db_name);
+
+    assert!(v1::Person::new(&db).is_ok());
+    drop(db);
+    assert!(v1::Person::new(&Database::open(db_name).unwrap()).is_ok());
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_new_detects_schema_change() {
+    let db_name = "migration_detect_db";
+    let (db, _) = setup(db_name);
+
+    match v2::Person::new(&db) {
+        Err(Error::SchemaMismatch { table, diff }) => {
+            assert_eq!(table, "Person");
+            assert_eq!(diff.added.iter().map(|f| f.name.as_str()).collect::<Vec<_>>(), ["email"]);
+            assert_eq!(diff.removed.iter().map(|f| f.name.as_str()).collect::<Vec<_>>(), ["nickname"]);
+            assert_eq!(diff.retyped.len(), 1);
+            assert_eq!(diff.retyped[0].0.type_, "u8");
+            assert_eq!(diff.retyped[0].1.type_, "u16");
+            assert!(!diff.reordered);
+        },
+        other => panic!("expected a schema mismatch, got {:?}", other),
+    }
+
+    let message = v2::Person::new(&db).unwrap_err().to_string();
+    assert_eq!(message, "The schema of table Person has changed; added: email; removed: nickname; retyped: age (u8 -> u16)");
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_migrate_converts_records() {
+    let db_name = "migration_convert_db";
+    let (db, oids) = setup(db_name);
+    v1::Person::delete(&v1::Person::get(&db, oids[1]).unwrap().unwrap(), &db).unwrap();
+
+    db.migrate(to_v2).unwrap();
+
+    assert!(v2::Person::new(&db).is_ok());
+    assert_eq!(db.read_schema("Person").unwrap().fields, v2::Person::get_fields());
+
+    // The OIDs are kept, the deleted record is gone
+    let people = v2::Person::select(&db).unwrap();
+    assert_eq!(people.len(), 2);
+    assert_eq!(people[0], v2::Person {
+        id: oids[0],
+        name: Primitive { val: "Ada".to_string() },
+        age: Primitive { val: 36 },
+        email: Primitive { val: "ada@example.com".to_string() },
+    });
+    assert_eq!(v2::Person::get(&db, oids[2]).unwrap().unwrap().age.val, 85);
+    assert!(v2::Person::get(&db, oids[1]).unwrap().is_none());
+
+    // New OIDs continue after the old ones
+    let oid = v2::Person {
+        id: OID { val: 0 },
+        name: Primitive { val: "Alan".to_string() },
+        age: Primitive { val: 41 },
+        email: Primitive { val: String::new() },
+    }.save(&db).unwrap();
+    assert_eq!(oid, OID { val: 4 });
+
+    // The migrated table is the one found when the database is opened again
+    drop(db);
+    let db = Database::open(db_name).unwrap();
+    assert!(v2::Person::new(&db).is_ok());
+    assert_eq!(v2::Person::select(&db).unwrap().len(), 3);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_migrate_error_keeps_table() {
+    let db_name = "migration_error_db";
+    let (db, oids) = setup(db_name);
+
+    let result = db.migrate(|old: RawRecord| {
+        if old.oid() == oids[2] {
+            return Err(Error::NotFound("Grace".to_string()));
+        }
+        to_v2(old)
+    });
+
+    assert!(matches!(result, Err(Error::NotFound(_))));
+    assert!(v1::Person::new(&db).is_ok());
+    assert_eq!(v1::Person::select(&db).unwrap().len(), 3);
+    assert!(!std::path::Path::new(db_name).join("Person.tbl.migration").exists());
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_migrate_interrupted_between_the_renames() {
+    let db_name = "migration_interrupted_db";
+    let (db, oids) = setup(db_name);
+    let dir = std::path::Path::new(db_name);
+
+    let old_tbl = fs::read(dir.join("Person.tbl")).unwrap();
+    let old_db_file = fs::read(dir.join(format!("{}.db", db_name))).unwrap();
+
+    db.migrate(to_v2).unwrap();
+    drop(db);
+
+    // A crash after the new bucket was moved into place, before the new .tbl and the catalog
+    fs::rename(dir.join("Person.tbl"), dir.join("Person.tbl.migration")).unwrap();
+    fs::write(dir.join("Person.tbl"), &old_tbl).unwrap();
+    fs::write(dir.join(format!("{}.db", db_name)), &old_db_file).unwrap();
+    fs::write(dir.join("Person.migration.ready"), b"").unwrap();
+
+    let db = Database::open(db_name).unwrap();
+
+    assert!(v2::Person::new(&db).is_ok());
+    assert!(db.check_catalog().unwrap().is_empty());
+    assert_eq!(v2::Person::get(&db, oids[1]).unwrap().unwrap().email.val, "linus@example.com");
+    assert_eq!(v2::Person::select(&db).unwrap().len(), 3);
+    assert!(!dir.join("Person.tbl.migration").exists());
+    assert!(!dir.join("Person.migration.ready").exists());
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_migrate_missing_field() {
+    let db_name = "migration_missing_field_db";
+    let (db, _) = setup(db_name);
+
+    let result = db.migrate(|old: RawRecord| {
+        Ok(v2::Person {
+            id: old.oid(),
+            name: Primitive { val: old.get("name")? },
+            age: Primitive { val: old.get::<u8>("age")? as u16 },
+            email: Primitive { val: old.get("email")? },
+        })
+    });
+
+    assert!(matches!(result, Err(Error::NotFound(ref name)) if name == "Field email of Person"));
+
+    // age is still a u8 in the old records
+    let result = db.migrate(|old: RawRecord| -> Result<v2::Person, Error> { old.get::<u16>("age").map(|_| unreachable!()) });
+
+    assert!(matches!(result, Err(Error::CorruptField { ref field, .. }) if field == "age"));
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+// Two versions of the table Article, the second one only adds fields with a default value
+mod article_v1 {
+    use objektdb::{Objekt, OID, Primitive};
+
+    #[derive(Objekt, Debug, PartialEq)]
+    pub struct Article {
+        pub id: OID,
+        pub title: Primitive<String>,
+    }
+}
+
+mod article_v2 {
+    use objektdb::{Objekt, OID, Primitive};
+
+    #[derive(Objekt, Debug, PartialEq)]
+    pub struct Article {
+        pub id: OID,
+        pub title: Primitive<String>,
+        #[objekt(default = "draft")]
+        pub status: Primitive<String>,
+        #[objekt(default = 1)]
+        pub revision: Primitive<u16>,
+        #[objekt(default)]
+        pub pinned: Primitive<bool>,
+    }
+}
+
+mod article_v3 {
+    use objektdb::{Objekt, OID, Primitive};
+
+    // A field without a default can't be added without a migration
+    #[derive(Objekt, Debug, PartialEq)]
+    pub struct Article {
+        pub id: OID,
+        pub title: Primitive<String>,
+        pub author: Primitive<String>,
+    }
+}
+
+fn setup_articles(db_name: &str) -> (Database, OID) {
+    let _ = fs::remove_dir_all(db_name);
+    let db = Database::create(db_name).unwrap();
+    article_v1::Article::new(&db).unwrap();
+
+    let oid = article_v1::Article {
+        id: OID { val: 0 },
+        title: Primitive { val: "Schemas".to_string() },
+    }.save(&db).unwrap();
+
+    (db, oid)
+}
+
+#[test]
+fn test_new_adds_fields_with_default() {
+    let db_name = "migration_defaults_db";
+    let (db, oid) = setup_articles(db_name);
+    let tbl_len = fs::metadata(std::path::Path::new(db_name).join("Article.tbl")).unwrap().len();
+
+    article_v2::Article::new(&db).unwrap();
+
+    let schema = db.read_schema("Article").unwrap();
+    assert_eq!(schema.version, 2);
+    assert_eq!(schema.fields, article_v2::Article::get_fields());
+    assert!(schema.fields[2].has_default && !schema.fields[1].has_default);
+
+    // The new fields fit in the space reserved in the header: nothing is moved
+    assert_eq!(fs::metadata(std::path::Path::new(db_name).join("Article.tbl")).unwrap().len(), tbl_len);
+
+    // The old record is decoded with the defaults, the new ones have all the fields
+    assert_eq!(article_v2::Article::get(&db, oid).unwrap().unwrap(), article_v2::Article {
+        id: oid,
+        title: Primitive { val: "Schemas".to_string() },
+        status: Primitive { val: "draft".to_string() },
+        revision: Primitive { val: 1 },
+        pinned: Primitive { val: false },
+    });
+
+    let new_oid = article_v2::Article {
+        id: OID { val: 0 },
+        title: Primitive { val: "Defaults".to_string() },
+        status: Primitive { val: "published".to_string() },
+        revision: Primitive { val: 3 },
+        pinned: Primitive { val: true },
+    }.save(&db).unwrap();
+    assert_eq!(new_oid, OID { val: 2 });
+    assert_eq!(article_v2::Article::get(&db, new_oid).unwrap().unwrap().revision.val, 3);
+
+    // The extended schema is the one found when the database is opened again
+    drop(db);
+    let db = Database::open(db_name).unwrap();
+    article_v2::Article::new(&db).unwrap();
+    assert_eq!(article_v2::Article::select(&db).unwrap().len(), 2);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_new_field_without_default_needs_migration() {
+    let db_name = "migration_no_default_db";
+    let (db, _) = setup_articles(db_name);
+
+    match article_v3::Article::new(&db) {
+        Err(Error::SchemaMismatch { diff, .. }) => {
+            assert_eq!(diff.added.len(), 1);
+            assert_eq!(diff.added[0].name, "author");
+        },
+        other => panic!("expected a schema mismatch, got {:?}", other),
+    }
+    assert_eq!(db.read_schema("Article").unwrap().version, 1);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_migrate_increments_schema_version() {
+    let db_name = "migration_version_db";
+    let (db, _) = setup(db_name);
+
+    db.migrate(to_v2).unwrap();
+
+    assert_eq!(db.read_schema("Person").unwrap().version, 2);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_migrate_adds_reference() {
+    let db_name = "migration_reference_db";
+    let (db, oids) = setup(db_name);
+
+    v3::Company::new(&db).unwrap();
+    let acme = v3::Company { id: OID { val: 0 }, name: Primitive { val: "Acme".to_string() } }.save(&db).unwrap();
+
+    db.migrate(|old: RawRecord| Ok(v3::Person {
+        id: old.oid(),
+        name: Primitive { val: old.get("name")? },
+        employer: objektdb::Ref::new(acme),
+    })).unwrap();
+
+    // The new reference restricts the deletion of its target, before Person::new() is called again
+    assert_eq!(db.read_schema("Person").unwrap().references, vec!["Company".to_string()]);
+    let result = db.delete_record("Company".to_string(), acme);
+    assert!(matches!(result, Err(Error::RestrictedDelete { ref by, by_oid, .. }) if by == "Person" && by_oid == oids[0]));
+    assert!(db.get_record("Company".to_string(), acme).unwrap().is_some());
+
+    assert!(v3::Person::new(&db).is_ok());
+    assert!(db.check_catalog().unwrap().is_empty());
+
+    fs::remove_dir_all(db_name).unwrap();
+}
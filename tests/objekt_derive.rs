@@ -0,0 +1,187 @@
+use objektdb::{
+    objektdb_core::traits::{from_bytes::DecodeError, objekt::Objekt},
+    Error, Objekt, OID, Primitive,
+};
+
+#[derive(Objekt, Debug, PartialEq)]
+struct AllPrimitives {
+    id: OID,
+    a: Primitive<i8>,
+    b: Primitive<i16>,
+    c: Primitive<i32>,
+    d: Primitive<i64>,
+    e: Primitive<i128>,
+    f: Primitive<u8>,
+    g: Primitive<u16>,
+    h: Primitive<u32>,
+    i: Primitive<u64>,
+    j: Primitive<u128>,
+    k: Primitive<f32>,
+    l: Primitive<f64>,
+    m: Primitive<bool>,
+    n: Primitive<char>,
+    o: Primitive<String>,
+    p: Primitive<usize>,
+    q: Primitive<isize>,
+}
+
+fn sample() -> AllPrimitives {
+    AllPrimitives {
+        id: OID { val: 42 },
+        a: Primitive { val: -8 },
+        b: Primitive { val: -1600 },
+        c: Primitive { val: -320_000 },
+        d: Primitive { val: i64::MIN },
+        e: Primitive { val: i128::MAX },
+        f: Primitive { val: 255 },
+        g: Primitive { val: 65_535 },
+        h: Primitive { val: 3_200_000 },
+        i: Primitive { val: u64::MAX },
+        j: Primitive { val: u128::MAX - 1 },
+        k: Primitive { val: 3.25 },
+        l: Primitive { val: -1.0e300 },
+        m: Primitive { val: true },
+        n: Primitive { val: 'ß' },
+        o: Primitive { val: "objektDB".to_string() },
+        p: Primitive { val: usize::MAX },
+        q: Primitive { val: isize::MIN },
+    }
+}
+
+#[test]
+fn test_to_bytes_round_trip() {
+    let record = sample();
+
+    let decoded = AllPrimitives::record_from_bytes(record.to_bytes().unwrap());
+
+    assert_eq!(decoded.unwrap(), record);
+}
+
+#[test]
+fn test_to_bytes_length_prefixed_layout() {
+    let mut record = sample();
+    record.o.val = String::new();
+
+    let bytes = record.to_bytes().unwrap();
+
+    // OID: i32
+    assert_eq!(&bytes[0..5], &[4, 42, 0, 0, 0]);
+    // i8, then i16
+    assert_eq!(&bytes[5..7], &[1, (-8i8) as u8]);
+    assert_eq!(&bytes[7..10], &[2, 0xC0, 0xF9]);
+
+    let empty = AllPrimitives::record_from_bytes(bytes).unwrap();
+    assert_eq!(empty.o.val, "");
+}
+
+#[test]
+fn test_record_from_bytes_reports_corrupt_field() {
+    let mut bytes = sample().to_bytes().unwrap();
+
+    // The String field o (1 byte of length, then "objektDB") comes before p and q
+    let o_start = bytes.len() - 9 - 9 - 9;
+    bytes[o_start + 1] = 0xFF;
+
+    match AllPrimitives::record_from_bytes(bytes) {
+        Err(Error::CorruptField { table, field, offset, error }) => {
+            assert_eq!(table, "AllPrimitives");
+            assert_eq!(field, "o");
+            assert_eq!(offset, o_start as u64 + 1);
+            assert_eq!(error, DecodeError::InvalidUtf8);
+        },
+        other => panic!("expected a corrupt field, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_record_from_bytes_reports_wrong_length() {
+    let mut bytes = sample().to_bytes().unwrap();
+
+    // The OID is 4 bytes long, make it 3
+    bytes[0] = 3;
+
+    match AllPrimitives::record_from_bytes(bytes) {
+        Err(Error::CorruptField { field, offset, error, .. }) => {
+            assert_eq!(field, "id");
+            assert_eq!(offset, 1);
+            assert_eq!(error, DecodeError::Length { expected: 4, found: 3 });
+        },
+        other => panic!("expected a corrupt field, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_record_from_bytes_reports_truncated_record() {
+    let bytes = sample().to_bytes().unwrap();
+
+    let result = AllPrimitives::record_from_bytes(bytes[..bytes.len() - 1].to_vec());
+
+    assert!(matches!(
+        result,
+        Err(Error::CorruptField { ref field, error: DecodeError::Truncated, .. }) if field == "q"
+    ));
+}
+
+#[test]
+fn test_to_bytes_pointer_sized_fields_take_8_bytes() {
+    let bytes = sample().to_bytes().unwrap();
+
+    // p and q are the last two fields
+    let p_start = bytes.len() - 9 - 9;
+    assert_eq!(bytes[p_start], 8);
+    assert_eq!(&bytes[p_start + 1..p_start + 9], &(usize::MAX as u64).to_le_bytes());
+    assert_eq!(bytes[p_start + 9], 8);
+    assert_eq!(&bytes[p_start + 10..], &(isize::MIN as i64).to_le_bytes());
+}
+
+#[derive(Objekt, Debug, PartialEq)]
+struct WithDefaults {
+    id: OID,
+    name: Primitive<String>,
+    #[objekt(default = "none")]
+    tag: Primitive<String>,
+    #[objekt(default = 7)]
+    level: Primitive<u32>,
+    #[objekt(default)]
+    score: Primitive<f64>,
+}
+
+#[test]
+fn test_record_from_bytes_fills_missing_fields_with_defaults() {
+    let record = WithDefaults {
+        id: OID { val: 1 },
+        name: Primitive { val: "objektDB".to_string() },
+        tag: Primitive { val: "db".to_string() },
+        level: Primitive { val: 2 },
+        score: Primitive { val: 0.5 },
+    };
+    let bytes = record.to_bytes().unwrap();
+
+    // A record written before tag, level and score were added: id and name only
+    let old = WithDefaults::record_from_bytes(bytes[..5 + 9].to_vec()).unwrap();
+
+    assert_eq!(old.name.val, "objektDB");
+    assert_eq!(old.tag.val, "none");
+    assert_eq!(old.level.val, 7);
+    assert_eq!(old.score.val, 0.0);
+
+    // The fields that are there are still decoded
+    let partial = WithDefaults::record_from_bytes(bytes[..5 + 9 + 3].to_vec()).unwrap();
+    assert_eq!(partial.tag.val, "db");
+    assert_eq!(partial.level.val, 7);
+
+    assert_eq!(WithDefaults::record_from_bytes(bytes).unwrap(), record);
+    assert!(WithDefaults::get_fields()[2].has_default);
+    assert!(!WithDefaults::get_fields()[1].has_default);
+}
+
+#[test]
+fn test_record_from_bytes_missing_field_without_default() {
+    // name has no default, it can't be missing
+    let result = WithDefaults::record_from_bytes(vec![4, 1, 0, 0, 0]);
+
+    assert!(matches!(
+        result,
+        Err(Error::CorruptField { ref field, error: DecodeError::Truncated, .. }) if field == "name"
+    ));
+}
@@ -0,0 +1,75 @@
+use objektdb::{crud::CRUD, objektdb_core::traits::objekt::Objekt, odb, Database, Error, Objekt, Set, OID, Primitive};
+use std::fs;
+
+#[derive(Objekt, Debug, PartialEq)]
+struct Book {
+    id: OID,
+    title: Primitive<String>,
+}
+
+#[derive(Objekt, Debug, PartialEq)]
+struct Author {
+    id: OID,
+    name: Primitive<String>,
+}
+
+#[odb("odb_new_db")]
+struct Library {
+    books: Set<Book>,
+    authors: Set<Author>,
+}
+
+#[test]
+fn test_odb_new_opens_or_creates() {
+    let _ = fs::remove_dir_all("odb_new_db");
+
+    let library = Library::new().unwrap();
+    let db = library.books.db();
+    assert_eq!(db.name(), "odb_new_db");
+
+    // A table for each Set
+    let names: Vec<String> = db.tables().unwrap().into_iter().map(|entry| entry.name).collect();
+    assert_eq!(names, vec!["Book".to_string(), "Author".to_string()]);
+
+    let oid = Book { id: OID { val: 0 }, title: Primitive { val: "Dune".to_string() } }.save(db).unwrap();
+    drop(library);
+
+    // The second time the existing database is opened
+    let library = Library::new().unwrap();
+    assert_eq!(Book::get(library.books.db(), oid).unwrap().unwrap().title.val, "Dune");
+    assert_eq!(library.authors.db().tables().unwrap().len(), 2);
+
+    drop(library);
+    fs::remove_dir_all("odb_new_db").unwrap();
+}
+
+#[odb("odb_open_db")]
+struct Shelf {
+    books: Set<Book>,
+}
+
+#[test]
+fn test_odb_open_propagates_errors() {
+    let parent = "odb_open_parent";
+    let _ = fs::remove_dir_all(parent);
+    fs::create_dir(parent).unwrap();
+    let path = std::path::Path::new(parent).join("shelf");
+
+    // The table of Book exists with other fields
+    let db = Database::create(&path).unwrap();
+    db.create_table("Book".to_string(), vec![], Author::get_fields(), vec![]).unwrap();
+    drop(db);
+
+    let result = Shelf::open(&path);
+
+    assert!(matches!(result, Err(Error::SchemaMismatch { ref table, .. }) if table == "Book"));
+
+    // Once the old table is gone, it's created again
+    Database::open(&path).unwrap().drop_table("Book").unwrap();
+    let shelf = Shelf::open(&path).unwrap();
+    assert_eq!(shelf.books.db().path(), path.as_path());
+    assert_eq!(shelf.books.db().read_schema("Book").unwrap().fields, Book::get_fields());
+    drop(shelf);
+
+    fs::remove_dir_all(parent).unwrap();
+}
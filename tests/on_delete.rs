@@ -0,0 +1,315 @@
+use objektdb::{crud::CRUD, objektdb_core::traits::objekt::Objekt, odb, Database, Error, Objekt, OnDelete, Ref, RefSet, Set, OID, Primitive};
+use std::fs;
+
+#[derive(Objekt, Debug, PartialEq)]
+struct Author {
+    id: OID,
+    name: Primitive<String>,
+    tags: RefSet<Tag>,
+}
+
+#[derive(Objekt, Debug, PartialEq)]
+struct Tag {
+    id: OID,
+    name: Primitive<String>,
+}
+
+#[derive(Objekt, Debug, PartialEq)]
+struct Post {
+    id: OID,
+    title: Primitive<String>,
+    #[objekt(on_delete = "cascade")]
+    author: Ref<Author>,
+    #[objekt(default, on_delete = "set_null")]
+    editor: Ref<Author>,
+}
+
+#[derive(Objekt, Debug, PartialEq)]
+struct Comment {
+    id: OID,
+    text: Primitive<String>,
+    #[objekt(on_delete = "restrict")]
+    post: Ref<Post>,
+}
+
+#[odb("on_delete_db")]
+struct Blog {
+    authors: Set<Author>,
+    tags: Set<Tag>,
+    posts: Set<Post>,
+    comments: Set<Comment>,
+}
+
+fn author(name: &str) -> Author {
+    Author { id: OID { val: 0 }, name: Primitive { val: name.to_string() }, tags: RefSet::default() }
+}
+
+fn post(title: &str, author: OID, editor: OID) -> Post {
+    Post {
+        id: OID { val: 0 },
+        title: Primitive { val: title.to_string() },
+        author: Ref::new(author),
+        editor: Ref::new(editor),
+    }
+}
+
+fn comment(text: &str, post: OID) -> Comment {
+    Comment { id: OID { val: 0 }, text: Primitive { val: text.to_string() }, post: Ref::new(post) }
+}
+
+/// Removes the directory of a database when the test ends, even if it fails.
+struct Cleanup(&'static str);
+
+impl Drop for Cleanup {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(self.0);
+    }
+}
+
+fn setup(db_name: &str) -> Blog {
+    let _ = fs::remove_dir_all(db_name);
+    Blog::open(db_name).unwrap()
+}
+
+#[test]
+fn test_on_delete_is_stored_in_the_schema() {
+    let db_name = "on_delete_schema_db";
+    let _cleanup = Cleanup(db_name);
+    let db = setup(db_name);
+
+    let schema = db.posts.db().read_schema("Post").unwrap();
+    assert_eq!(schema.fields[2].on_delete, OnDelete::Cascade);
+    assert_eq!(schema.fields[3].on_delete, OnDelete::SetNull);
+    assert_eq!(Comment::get_fields()[2].on_delete, OnDelete::Restrict);
+    assert_eq!(Author::get_fields()[1].on_delete, OnDelete::Restrict);
+
+    // Changing a policy changes the schema hash
+    let mut changed = schema.clone();
+    changed.fields[2].on_delete = OnDelete::Restrict;
+    assert_ne!(changed.schema_hash(), schema.schema_hash());
+}
+
+#[test]
+fn test_on_delete_restrict() {
+    let db_name = "on_delete_restrict_db";
+    let _cleanup = Cleanup(db_name);
+    let db = setup(db_name);
+
+    let ada = db.authors.push(author("Ada")).unwrap();
+    let first = db.posts.push(post("First", ada, OID { val: 0 })).unwrap();
+    let reply = db.comments.push(comment("Nice", first)).unwrap();
+
+    let result = db.posts.remove(first);
+
+    assert!(matches!(
+        result,
+        Err(Error::RestrictedDelete { ref table, oid, ref by, by_oid, ref field })
+            if table == "Post" && oid == first && by == "Comment" && by_oid == reply && field == "post"
+    ));
+    assert!(db.posts.get(first).unwrap().is_some());
+
+    // Once the comment is gone, the post can be deleted
+    assert!(db.comments.remove(reply).unwrap());
+    assert!(db.posts.remove(first).unwrap());
+}
+
+#[test]
+fn test_on_delete_cascade_and_set_null() {
+    let db_name = "on_delete_cascade_db";
+    let _cleanup = Cleanup(db_name);
+    let db = setup(db_name);
+
+    let ada = db.authors.push(author("Ada")).unwrap();
+    let grace = db.authors.push(author("Grace")).unwrap();
+    let first = db.posts.push(post("First", ada, grace)).unwrap();
+    let second = db.posts.push(post("Second", grace, ada)).unwrap();
+    let third = db.posts.push(post("Third", ada, ada)).unwrap();
+
+    assert!(db.authors.remove(ada).unwrap());
+
+    // The posts of Ada are gone, her edits are not
+    assert!(db.posts.get(first).unwrap().is_none());
+    assert!(db.posts.get(third).unwrap().is_none());
+
+    let second = db.posts.get(second).unwrap().unwrap();
+    assert_eq!(second.title.val, "Second");
+    assert_eq!(second.author.oid, grace);
+    assert!(second.editor.is_null());
+    assert_eq!(db.posts.len().unwrap(), 1);
+}
+
+#[test]
+fn test_on_delete_nested_restrict_aborts_everything() {
+    let db_name = "on_delete_nested_db";
+    let _cleanup = Cleanup(db_name);
+    let db = setup(db_name);
+
+    let ada = db.authors.push(author("Ada")).unwrap();
+    let grace = db.authors.push(author("Grace")).unwrap();
+    let first = db.posts.push(post("First", ada, OID { val: 0 })).unwrap();
+    let second = db.posts.push(post("Second", grace, ada)).unwrap();
+    db.comments.push(comment("Nice", first)).unwrap();
+
+    // Deleting Ada would delete her post, which has a comment
+    let result = db.authors.remove(ada);
+
+    assert!(matches!(result, Err(Error::RestrictedDelete { ref table, oid, .. }) if table == "Post" && oid == first));
+    assert!(db.authors.get(ada).unwrap().is_some());
+    assert!(db.posts.get(first).unwrap().is_some());
+    assert_eq!(db.posts.get(second).unwrap().unwrap().editor.oid, ada);
+}
+
+#[test]
+fn test_on_delete_ref_set_rows_follow_their_objects() {
+    let db_name = "on_delete_ref_set_db";
+    let _cleanup = Cleanup(db_name);
+    let db = setup(db_name);
+
+    let rust = db.tags.push(Tag { id: OID { val: 0 }, name: Primitive { val: "rust".to_string() } }).unwrap();
+    let db_tag = db.tags.push(Tag { id: OID { val: 0 }, name: Primitive { val: "db".to_string() } }).unwrap();
+    let ada = db.authors.push(author("Ada")).unwrap();
+    let ada = db.authors.get(ada).unwrap().unwrap();
+    ada.tags.add(rust).unwrap();
+    ada.tags.add(db_tag).unwrap();
+
+    // Deleting a target removes its join rows
+    assert!(db.tags.remove(rust).unwrap());
+    assert_eq!(ada.tags.oids().unwrap(), vec![db_tag]);
+
+    // So does deleting the owner
+    assert!(db.authors.remove(ada.id).unwrap());
+    assert_eq!(db.authors.db().count_records("Author.tags".to_string()).unwrap(), 0);
+    assert!(db.tags.get(db_tag).unwrap().is_some());
+}
+
+#[derive(Objekt, Debug, PartialEq)]
+struct Writer {
+    id: OID,
+    name: Primitive<String>,
+}
+
+// Two versions of the same struct, stored in the table Article: the second one adds a reference
+mod v1 {
+    use objektdb::{Objekt, OID, Primitive};
+
+    #[derive(Objekt, Debug, PartialEq)]
+    pub struct Article {
+        pub id: OID,
+        pub title: Primitive<String>,
+    }
+}
+
+mod v2 {
+    use objektdb::{Objekt, Ref, OID, Primitive};
+
+    #[derive(Objekt, Debug, PartialEq)]
+    pub struct Article {
+        pub id: OID,
+        pub title: Primitive<String>,
+        #[objekt(default, on_delete = "set_null")]
+        pub editor: Ref<super::Writer>,
+    }
+}
+
+#[test]
+fn test_on_delete_records_written_before_the_reference() {
+    let db_name = "on_delete_added_ref_db";
+    let _cleanup = Cleanup(db_name);
+    let _ = fs::remove_dir_all(db_name);
+    let db = Database::create(db_name).unwrap();
+    Writer::new(&db).unwrap();
+    v1::Article::new(&db).unwrap();
+
+    let ada = Writer { id: OID { val: 0 }, name: Primitive { val: "Ada".to_string() } }.save(&db).unwrap();
+    let old = v1::Article { id: OID { val: 0 }, title: Primitive { val: "Old".to_string() } }.save(&db).unwrap();
+
+    // The reference is added with a default, without migrating the table
+    v2::Article::new(&db).unwrap();
+    let new = v2::Article {
+        id: OID { val: 0 },
+        title: Primitive { val: "New".to_string() },
+        editor: Ref::new(ada),
+    }.save(&db).unwrap();
+
+    // The old record has no value for the reference, so it doesn't refer to Ada
+    assert!(db.delete_record("Writer".to_string(), ada).unwrap());
+
+    assert!(v2::Article::get(&db, old).unwrap().unwrap().editor.is_null());
+    assert!(v2::Article::get(&db, new).unwrap().unwrap().editor.is_null());
+}
+
+#[test]
+fn test_on_delete_after_renaming_the_target_table() {
+    let db_name = "on_delete_renamed_db";
+    let _cleanup = Cleanup(db_name);
+    let db = setup(db_name);
+    let database = db.posts.db();
+
+    let ada = db.authors.push(author("Ada")).unwrap();
+    let first = db.posts.push(post("First", ada, OID { val: 0 })).unwrap();
+    db.comments.push(comment("Nice", first)).unwrap();
+
+    database.rename_table("Author", "Writer").unwrap();
+    database.rename_table("Post", "Entry").unwrap();
+
+    // The references follow the tables
+    let schema = database.read_schema("Entry").unwrap();
+    assert_eq!(schema.fields[2].type_, "Ref<Writer>");
+    assert_eq!(schema.fields[3].type_, "Ref<Writer>");
+    assert_eq!(database.read_schema("Comment").unwrap().fields[2].type_, "Ref<Entry>");
+    assert!(database.check_catalog().unwrap().is_empty());
+
+    // So do the policies: the cascade reaches the comment, which restricts it
+    let result = database.delete_record("Writer".to_string(), ada);
+    assert!(matches!(result, Err(Error::RestrictedDelete { ref table, ref by, .. }) if table == "Entry" && by == "Comment"));
+    assert!(database.get_record("Writer".to_string(), ada).unwrap().is_some());
+}
+
+#[derive(Objekt, Debug, PartialEq)]
+struct Node {
+    id: OID,
+    #[objekt(default, on_delete = "cascade")]
+    parent: Ref<Node>,
+}
+
+#[test]
+fn test_on_delete_long_cascade_chain() {
+    let db_name = "on_delete_chain_db";
+    let _cleanup = Cleanup(db_name);
+    let _ = fs::remove_dir_all(db_name);
+    let db = Database::create(db_name).unwrap();
+    Node::new(&db).unwrap();
+
+    // Each node refers to the one before it
+    let mut parent = OID { val: 0 };
+    let mut nodes = Vec::new();
+    for _ in 0..500 {
+        parent = Node { id: OID { val: 0 }, parent: Ref::new(parent) }.save(&db).unwrap();
+        nodes.push(parent);
+    }
+
+    assert!(db.delete_record("Node".to_string(), nodes[100]).unwrap());
+
+    assert_eq!(db.count_records("Node".to_string()).unwrap(), 100);
+    assert!(db.get_record("Node".to_string(), nodes[99]).unwrap().is_some());
+    assert!(db.get_record("Node".to_string(), nodes[499]).unwrap().is_none());
+}
+
+#[test]
+fn test_on_delete_with_a_missing_table() {
+    let db_name = "on_delete_missing_table_db";
+    let _cleanup = Cleanup(db_name);
+    let db = setup(db_name);
+    let database = db.posts.db();
+    database.create_table("Note".to_string(), vec![], vec![], vec![]).unwrap();
+
+    let ada = db.authors.push(author("Ada")).unwrap();
+    let first = db.posts.push(post("First", ada, OID { val: 0 })).unwrap();
+
+    // The files of a table that doesn't refer to Author are lost
+    fs::remove_file(std::path::Path::new(db_name).join("Note.tbl")).unwrap();
+    assert_eq!(database.check_catalog().unwrap().missing, vec!["Note".to_string()]);
+
+    assert!(db.authors.remove(ada).unwrap());
+    assert!(db.posts.get(first).unwrap().is_none());
+}
@@ -0,0 +1,162 @@
+use objektdb::{objektdb_core::traits::objekt::Objekt, odb, Error, Objekt, RefSet, Set, OID, Primitive};
+use std::fs;
+
+#[derive(Objekt, Debug, PartialEq)]
+struct Group {
+    id: OID,
+    name: Primitive<String>,
+}
+
+#[derive(Objekt, Debug, PartialEq)]
+struct User {
+    id: OID,
+    name: Primitive<String>,
+    groups: RefSet<Group>,
+}
+
+#[odb("ref_set_db")]
+struct Directory {
+    groups: Set<Group>,
+    users: Set<User>,
+}
+
+fn group(name: &str) -> Group {
+    Group { id: OID { val: 0 }, name: Primitive { val: name.to_string() } }
+}
+
+fn user(name: &str) -> User {
+    User { id: OID { val: 0 }, name: Primitive { val: name.to_string() }, groups: RefSet::default() }
+}
+
+fn setup(db_name: &str) -> Directory {
+    let _ = fs::remove_dir_all(db_name);
+    Directory::open(db_name).unwrap()
+}
+
+#[test]
+fn test_ref_set_join_table() {
+    let db_name = "ref_set_join_db";
+    let db = setup(db_name);
+    let database = db.users.db();
+
+    // The set is not a field of the record
+    assert_eq!(User::get_fields().iter().map(|field| field.name.as_str()).collect::<Vec<_>>(), vec!["id", "name"]);
+    assert_eq!(user("Ada").to_bytes().unwrap().len(), 5 + 4);
+
+    let schema = database.read_schema("User.groups").unwrap();
+    assert_eq!(schema.references, vec!["User".to_string(), "Group".to_string()]);
+    assert_eq!(schema.fields, RefSet::<Group>::join_fields("User"));
+    assert!(schema.fields[1].is_fk && schema.fields[2].is_fk);
+    assert!(database.tables().unwrap().iter().any(|entry| entry.file_name == "User.groups.tbl"));
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_ref_set_add_iter_remove() {
+    let db_name = "ref_set_add_db";
+    let db = setup(db_name);
+
+    let admins = db.groups.push(group("admins")).unwrap();
+    let staff = db.groups.push(group("staff")).unwrap();
+    let ada = db.users.push(user("Ada")).unwrap();
+    let linus = db.users.push(user("Linus")).unwrap();
+
+    let ada = db.users.get(ada).unwrap().unwrap();
+    let linus = db.users.get(linus).unwrap().unwrap();
+    assert!(ada.groups.is_empty().unwrap());
+
+    assert!(ada.groups.add(admins).unwrap());
+    assert!(ada.groups.add(staff).unwrap());
+    assert!(!ada.groups.add(admins).unwrap());
+    assert!(linus.groups.add(staff).unwrap());
+
+    // Only the groups that exist can be added
+    assert!(matches!(ada.groups.add(OID { val: 99 }), Err(Error::NotFound(_))));
+    assert_eq!(ada.groups.oids().unwrap(), vec![admins, staff]);
+
+    // Many to many: each user has its own groups, a group has many users
+    let names: Vec<String> = ada.groups.iter().unwrap().map(|group| group.unwrap().name.val).collect();
+    assert_eq!(names, vec!["admins", "staff"]);
+    assert_eq!(linus.groups.oids().unwrap(), vec![staff]);
+
+    assert!(ada.groups.remove(admins).unwrap());
+    assert!(!ada.groups.remove(admins).unwrap());
+    assert_eq!(ada.groups.oids().unwrap(), vec![staff]);
+    assert!(!ada.groups.contains(admins).unwrap());
+
+    // The group itself is still there
+    assert!(db.groups.get(admins).unwrap().is_some());
+
+    // Read again, the set has the same content
+    let again = db.users.get(ada.id).unwrap().unwrap();
+    assert_eq!(again.groups.len().unwrap(), 1);
+    assert_eq!(again.groups, ada.groups);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_ref_set_skips_deleted_objects() {
+    let db_name = "ref_set_deleted_db";
+    let db = setup(db_name);
+
+    let admins = db.groups.push(group("admins")).unwrap();
+    let staff = db.groups.push(group("staff")).unwrap();
+    let ada = db.users.push(user("Ada")).unwrap();
+    let ada = db.users.get(ada).unwrap().unwrap();
+    ada.groups.add(admins).unwrap();
+    ada.groups.add(staff).unwrap();
+
+    db.groups.remove(admins).unwrap();
+
+    let groups: Vec<Group> = ada.groups.iter().unwrap().collect::<Result<_, _>>().unwrap();
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0].name.val, "staff");
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_ref_set_unbound() {
+    let set: RefSet<Group> = RefSet::default();
+
+    assert!(matches!(set.add(OID { val: 1 }), Err(Error::Unbound(ref table)) if table == "Group"));
+    assert!(matches!(set.oids(), Err(Error::Unbound(_))));
+}
+
+#[test]
+fn test_ref_set_follows_other_writes_to_the_join_table() {
+    let db_name = "ref_set_writes_db";
+    let db = setup(db_name);
+    let database = db.users.db();
+
+    let groups: Vec<OID> = (0..50).map(|i| db.groups.push(group(&format!("group {}", i))).unwrap()).collect();
+    let ada = db.users.push(user("Ada")).unwrap();
+    let ada = db.users.get(ada).unwrap().unwrap();
+
+    for oid in &groups {
+        assert!(ada.groups.add(*oid).unwrap());
+    }
+    assert!(!ada.groups.add(groups[10]).unwrap());
+    assert_eq!(ada.groups.oids().unwrap(), groups);
+
+    // A row written without the set
+    let extra = db.groups.push(group("extra")).unwrap();
+    let mut row = Vec::new();
+    for val in [0i32, ada.id.val, extra.val] {
+        row.push(4);
+        row.extend_from_slice(&val.to_le_bytes());
+    }
+    database.insert_record("User.groups".to_string(), row).unwrap();
+    assert!(ada.groups.contains(extra).unwrap());
+
+    // A row deleted by a cascade
+    assert!(db.groups.remove(groups[0]).unwrap());
+    assert!(!ada.groups.contains(groups[0]).unwrap());
+    assert!(ada.groups.remove(groups[1]).unwrap());
+    assert_eq!(ada.groups.len().unwrap(), 49);
+    assert_eq!(database.count_records("User.groups".to_string()).unwrap(), 49);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
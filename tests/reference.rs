@@ -0,0 +1,140 @@
+use objektdb::{objektdb_core::traits::objekt::Objekt, odb, Error, Objekt, Ref, Set, OID, Primitive};
+use std::fs;
+
+#[derive(Objekt, Debug, PartialEq)]
+struct Company {
+    id: OID,
+    name: Primitive<String>,
+}
+
+#[derive(Objekt, Debug, PartialEq)]
+struct Person {
+    id: OID,
+    name: Primitive<String>,
+    employer: Ref<Company>,
+    #[objekt(default)]
+    previous_employer: Ref<Company>,
+}
+
+#[odb("reference_db")]
+struct Registry {
+    companies: Set<Company>,
+    people: Set<Person>,
+}
+
+fn company(name: &str) -> Company {
+    Company { id: OID { val: 0 }, name: Primitive { val: name.to_string() } }
+}
+
+fn person(name: &str, employer: OID) -> Person {
+    Person {
+        id: OID { val: 0 },
+        name: Primitive { val: name.to_string() },
+        employer: Ref::new(employer),
+        previous_employer: Ref::default(),
+    }
+}
+
+fn setup(db_name: &str) -> Registry {
+    let _ = fs::remove_dir_all(db_name);
+    Registry::open(db_name).unwrap()
+}
+
+#[test]
+fn test_ref_is_a_foreign_key() {
+    let db_name = "reference_schema_db";
+    let db = setup(db_name);
+
+    let schema = db.people.db().read_schema("Person").unwrap();
+
+    assert_eq!(schema.references, vec!["Company".to_string()]);
+    assert!(schema.fields[2].is_fk);
+    assert_eq!(schema.fields[2].type_, "Ref<Company>");
+    assert!(!schema.fields[1].is_fk);
+    assert!(db.companies.db().read_schema("Company").unwrap().references.is_empty());
+
+    // The reference is stored as the OID of the target
+    let bytes = person("Ada", OID { val: 7 }).to_bytes().unwrap();
+    assert_eq!(&bytes[bytes.len() - 10..], &[4, 7, 0, 0, 0, 4, 0, 0, 0, 0]);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_ref_load() {
+    let db_name = "reference_load_db";
+    let db = setup(db_name);
+
+    let acme = db.companies.push(company("Acme")).unwrap();
+    let initech = db.companies.push(company("Initech")).unwrap();
+    let ada = db.people.push(person("Ada", acme)).unwrap();
+    db.people.push(person("Peter", initech)).unwrap();
+
+    let read = db.people.get(ada).unwrap().unwrap();
+    assert_eq!(read.employer.oid, acme);
+    assert_eq!(read.employer.load().unwrap().unwrap().name.val, "Acme");
+
+    // The objects read by a scan are bound too
+    let employers: Vec<String> = db.people.iter().unwrap()
+        .map(|person| person.unwrap().employer.load().unwrap().unwrap().name.val)
+        .collect();
+    assert_eq!(employers, vec!["Acme", "Initech"]);
+
+    // The target is read when load() is called, not with the object
+    let renamed = Company { id: acme, name: Primitive { val: "Acme Corp".to_string() } };
+    db.companies.db().update_record("Company".to_string(), acme, renamed.to_bytes().unwrap()).unwrap();
+    assert_eq!(read.employer.load().unwrap().unwrap().name.val, "Acme Corp");
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_ref_null_and_unbound() {
+    let db_name = "reference_null_db";
+    let db = setup(db_name);
+
+    let acme = db.companies.push(company("Acme")).unwrap();
+    let ada = db.people.push(person("Ada", acme)).unwrap();
+
+    let read = db.people.get(ada).unwrap().unwrap();
+    assert!(read.previous_employer.is_null());
+    assert_eq!(read.previous_employer.load().unwrap(), None);
+
+    // A reference built by hand doesn't know its database
+    let unbound: Ref<Company> = Ref::new(acme);
+    assert!(matches!(unbound.load(), Err(Error::Unbound(ref table)) if table == "Company"));
+    assert_eq!(unbound.load_from(db.companies.db()).unwrap().unwrap().name.val, "Acme");
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_ref_target_table_cannot_be_dropped() {
+    let db_name = "reference_drop_db";
+    let db = setup(db_name);
+
+    let result = db.companies.db().drop_table("Company");
+
+    assert!(matches!(result, Err(Error::Referenced { ref table, ref by }) if table == "Company" && by == "Person"));
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_ref_to_reinitialized_table_stays_dangling() {
+    let db_name = "reference_reinitialized_db";
+    let db = setup(db_name);
+    let database = db.companies.db();
+
+    let acme = db.companies.push(company("Acme")).unwrap();
+    let ada = db.people.push(person("Ada", acme)).unwrap();
+
+    database.reinitialize_table("Company".to_string(), vec![], Company::get_fields(), vec![]).unwrap();
+    let evil = db.companies.push(company("Evil Corp")).unwrap();
+
+    // The new object doesn't take the OID of the old one
+    assert_ne!(evil, acme);
+    assert_eq!(db.people.get(ada).unwrap().unwrap().employer.load().unwrap(), None);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
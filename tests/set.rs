@@ -0,0 +1,113 @@
+use objektdb::{odb, Database, Error, Objekt, Set, OID, Primitive};
+use std::fs;
+
+#[derive(Objekt, Debug, PartialEq)]
+struct Person {
+    id: OID,
+    name: Primitive<String>,
+    age: Primitive<u8>,
+}
+
+fn person(name: &str, age: u8) -> Person {
+    Person {
+        id: OID { val: 0 },
+        name: Primitive { val: name.to_string() },
+        age: Primitive { val: age },
+    }
+}
+
+#[odb("set_db")]
+struct People {
+    people: Set<Person>,
+}
+
+fn setup(db_name: &str) -> People {
+    let _ = fs::remove_dir_all(db_name);
+    People::open(db_name).unwrap()
+}
+
+#[test]
+fn test_set_push_and_get() {
+    let db_name = "set_push_db";
+    let db = setup(db_name);
+
+    let ada = db.people.push(person("Ada", 36)).unwrap();
+    let linus = db.people.push(person("Linus", 54)).unwrap();
+
+    assert_eq!(ada, OID { val: 1 });
+    assert_eq!(linus, OID { val: 2 });
+
+    let read = db.people.get(linus).unwrap().unwrap();
+    assert_eq!(read.id, linus);
+    assert_eq!(read.name.val, "Linus");
+    assert_eq!(db.people.get(OID { val: 3 }).unwrap(), None);
+
+    // A value longer than 255 bytes is refused, not truncated
+    let result = db.people.push(person(&"x".repeat(300), 1));
+    assert!(matches!(result, Err(Error::ValueTooLong { ref field, len: 300, .. }) if field == "name"));
+    assert_eq!(db.people.len().unwrap(), 2);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_set_push_persists_immediately() {
+    let db_name = "set_persist_db";
+    let db = setup(db_name);
+
+    let oid = db.people.push(person("Grace", 85)).unwrap();
+
+    // Another handle to the same database sees the object
+    let other = Database::open(db_name).unwrap();
+    assert_eq!(other.get_object::<Person>(oid).unwrap().unwrap().name.val, "Grace");
+    drop(other);
+    drop(db);
+
+    let db = People::open(db_name).unwrap();
+    assert_eq!(db.people.len().unwrap(), 1);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_set_remove() {
+    let db_name = "set_remove_db";
+    let db = setup(db_name);
+
+    let ada = db.people.push(person("Ada", 36)).unwrap();
+    db.people.push(person("Linus", 54)).unwrap();
+
+    assert!(db.people.remove(ada).unwrap());
+    assert!(!db.people.remove(ada).unwrap());
+
+    assert_eq!(db.people.get(ada).unwrap(), None);
+    assert_eq!(db.people.len().unwrap(), 1);
+
+    fs::remove_dir_all(db_name).unwrap();
+}
+
+#[test]
+fn test_set_iter_len_and_filter() {
+    let db_name = "set_iter_db";
+    let db = setup(db_name);
+
+    assert!(db.people.is_empty().unwrap());
+    assert_eq!(db.people.iter().unwrap().count(), 0);
+
+    for (name, age) in [("Ada", 36), ("Linus", 54), ("Grace", 85)] {
+        db.people.push(person(name, age)).unwrap();
+    }
+
+    assert_eq!(db.people.len().unwrap(), 3);
+    assert!(!db.people.is_empty().unwrap());
+
+    let names: Vec<String> = db.people.iter().unwrap()
+        .map(|person| person.unwrap().name.val)
+        .collect();
+    assert_eq!(names, vec!["Ada", "Linus", "Grace"]);
+
+    let over_50 = db.people.filter(|person| person.age.val > 50).unwrap();
+    assert_eq!(over_50.iter().map(|person| person.id.val).collect::<Vec<_>>(), vec![2, 3]);
+
+    fs::remove_dir_all(db_name).unwrap();
+}